@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use super::super::errors::ServerError;
+use super::super::profile_server::util::{
+    delete_accounts_from_db, get_account_from_db, get_player_from_db, make_account_json,
+};
+use super::super::state::AppState;
+use super::super::validated_query::ValidatedQuery;
+use super::params::PageParams;
+use super::realms::find_realm;
+use super::views::{AccountSummaryView, AccountView, PageView, PlayerView};
+use entity::{Account, AccountColumn, Player};
+
+pub async fn list_accounts_handler(
+    State(state): State<AppState>,
+    Path(realm_name): Path<String>,
+    ValidatedQuery(params): ValidatedQuery<PageParams>,
+) -> Result<Json<PageView<AccountSummaryView>>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let query = Account::find()
+        .filter(AccountColumn::RealmId.eq(realm.id))
+        .order_by_asc(AccountColumn::Hash);
+    let total = query.clone().count(&state.db).await?;
+    let accounts = query
+        .find_also_related(Player)
+        .offset(params.offset)
+        .limit(params.limit)
+        .all(&state.db)
+        .await?;
+    let items = accounts
+        .iter()
+        .map(|(account, player)| AccountSummaryView::new(account, player.as_ref()))
+        .collect();
+    Ok(Json(PageView { items, total, offset: params.offset, limit: params.limit }))
+}
+
+pub async fn get_account_handler(
+    State(state): State<AppState>,
+    Path((realm_name, player_hash)): Path<(String, i64)>,
+) -> Result<Json<AccountView>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let not_found = || ServerError::NotFound(format!("account ('{}', {player_hash})", realm.name));
+    // read straight from the db, the cache only holds what the game servers have asked for
+    let player = get_player_from_db(&state.db, player_hash).await?.ok_or_else(not_found)?;
+    let account = get_account_from_db(&state.db, realm.id, player_hash).await?.ok_or_else(not_found)?;
+    let (player, account) = (Arc::new(player), Arc::new(account));
+    Ok(Json(AccountView {
+        realm: realm.name.to_owned(),
+        player: PlayerView::from(player.as_ref()),
+        account: make_account_json(&player, &account)?,
+    }))
+}
+
+pub async fn delete_account_handler(
+    State(state): State<AppState>,
+    Path((realm_name, player_hash)): Path<(String, i64)>,
+) -> Result<StatusCode, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let realm_ids = delete_accounts_from_db(&state.db, player_hash, Some(realm.id)).await?;
+    if realm_ids.is_empty() {
+        return Err(ServerError::NotFound(format!("account ('{}', {player_hash})", realm.name)));
+    }
+    state.cache.invalidate_account(realm.id, player_hash).await;
+    tracing::info!("admin deleted account ('{}', {player_hash})", realm.name);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+
+use super::super::client_addr::ClientAddr;
+use super::super::errors::ServerError;
+use super::super::profile_server::anomaly::{
+    anomaly_account, dismiss_anomaly, get_anomaly, is_releasable, list_anomalies, release_anomaly,
+};
+use super::super::profile_server::util::{get_player_from_db, make_account_json};
+use super::super::state::AppState;
+use super::super::validated_query::ValidatedQuery;
+use super::params::AnomalyListParams;
+use super::realms::find_realm;
+use super::views::{AnomalyDetailView, AnomalyView, PageView};
+use entity::AccountAnomalyModel;
+
+async fn find_anomaly(state: &AppState, anomaly_id: i32) -> Result<AccountAnomalyModel, ServerError> {
+    get_anomaly(&state.db, anomaly_id)
+        .await?
+        .ok_or_else(|| ServerError::NotFound(format!("anomaly {anomaly_id}")))
+}
+
+async fn make_anomaly_detail_view(
+    state: &AppState,
+    anomaly: &AccountAnomalyModel,
+) -> Result<AnomalyDetailView, ServerError> {
+    let player = get_player_from_db(&state.db, anomaly.hash)
+        .await?
+        .ok_or_else(|| ServerError::NotFound(format!("player [hash:{}]", anomaly.hash)))?;
+    let (player, account) = (Arc::new(player), Arc::new(anomaly_account(anomaly)?));
+    Ok(AnomalyDetailView {
+        anomaly: AnomalyView::new(anomaly)?,
+        account: make_account_json(&player, &account)?,
+    })
+}
+
+pub async fn list_anomalies_handler(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AnomalyListParams>,
+) -> Result<Json<PageView<AnomalyView>>, ServerError> {
+    let realm_id = match &params.realm {
+        Some(realm_name) => Some(find_realm(&state, realm_name).await?.id),
+        None => None,
+    };
+    let (anomalies, total) =
+        list_anomalies(&state.db, realm_id, params.all, params.offset, params.limit).await?;
+    let items = anomalies.iter().map(AnomalyView::new).collect::<Result<_, _>>()?;
+    Ok(Json(PageView { items, total, offset: params.offset, limit: params.limit }))
+}
+
+pub async fn get_anomaly_handler(
+    State(state): State<AppState>,
+    Path(anomaly_id): Path<i32>,
+) -> Result<Json<AnomalyDetailView>, ServerError> {
+    let anomaly = find_anomaly(&state, anomaly_id).await?;
+    Ok(Json(make_anomaly_detail_view(&state, &anomaly).await?))
+}
+
+pub async fn release_anomaly_handler(
+    ClientAddr(client_ip): ClientAddr,
+    State(state): State<AppState>,
+    Path(anomaly_id): Path<i32>,
+) -> Result<Json<AnomalyDetailView>, ServerError> {
+    let anomaly = find_anomaly(&state, anomaly_id).await?;
+    if !is_releasable(&anomaly) {
+        return Err(ServerError::Conflict(format!(
+            "anomaly {anomaly_id} is not a quarantined save awaiting review"
+        )));
+    }
+    let actor = format!("admin '{client_ip}'");
+    release_anomaly(&state.db, &anomaly, &actor, &state.config()).await?;
+    state.cache.invalidate_account(anomaly.realm_id, anomaly.hash).await;
+    tracing::info!("{actor} released quarantined save {anomaly_id} for account [{},{}]", anomaly.realm_id, anomaly.hash);
+    let anomaly = find_anomaly(&state, anomaly_id).await?;
+    Ok(Json(make_anomaly_detail_view(&state, &anomaly).await?))
+}
+
+pub async fn dismiss_anomaly_handler(
+    ClientAddr(client_ip): ClientAddr,
+    State(state): State<AppState>,
+    Path(anomaly_id): Path<i32>,
+) -> Result<Json<AnomalyView>, ServerError> {
+    let anomaly = find_anomaly(&state, anomaly_id).await?;
+    if anomaly.resolution.is_some() {
+        return Err(ServerError::Conflict(format!("anomaly {anomaly_id} has already been resolved")));
+    }
+    let actor = format!("admin '{client_ip}'");
+    let anomaly = dismiss_anomaly(&state.db, &anomaly, &actor).await?;
+    tracing::info!("{actor} dismissed anomaly {anomaly_id}");
+    Ok(Json(AnomalyView::new(&anomaly)?))
+}
@@ -0,0 +1,31 @@
+use axum::extract::State;
+use axum::http::{header, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use super::super::errors::ServerError;
+use super::super::profile_server::util::digest_ok;
+use super::super::state::AppState;
+
+pub async fn require_admin_token<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ServerError> {
+    // the admin router is only mounted when a token is configured, but never fail open
+    let app_config = state.config();
+    let Some(admin_token) = &app_config.admin_token else {
+        return Err(ServerError::AdminTokenIncorrect);
+    };
+    let given_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !digest_ok(given_token, admin_token) {
+        tracing::warn!("admin request for '{}' rejected, bad token", request.uri().path());
+        return Err(ServerError::AdminTokenIncorrect);
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,79 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use chrono::{Duration, Utc};
+
+use super::super::client_addr::ClientAddr;
+use super::super::errors::ServerError;
+use super::super::profile_server::bans::{create_ban, get_ban, is_active, lift_ban, list_bans, BanTarget};
+use super::super::state::AppState;
+use super::super::validated_query::{ValidatedJson, ValidatedQuery};
+use super::params::{BanListParams, BanParams};
+use super::views::{BanView, PageView};
+use entity::BanModel;
+
+async fn find_ban(state: &AppState, ban_id: i32) -> Result<BanModel, ServerError> {
+    get_ban(&state.db, ban_id)
+        .await?
+        .ok_or_else(|| ServerError::NotFound(format!("ban {ban_id}")))
+}
+
+fn ban_target(params: &BanParams) -> BanTarget {
+    // the params have been validated to hold exactly one target, and a usable ip if that's the one
+    match (params.sid, &params.username, params.hash, &params.ip) {
+        (Some(sid), _, _, _) => BanTarget::Sid(sid),
+        (_, Some(username), _, _) => BanTarget::username(username),
+        (_, _, Some(hash), _) => BanTarget::Hash(hash),
+        (_, _, _, Some(ip)) => BanTarget::ip(ip).expect("ban ip is validated"),
+        _ => unreachable!("ban params are validated to hold a target"),
+    }
+}
+
+pub async fn list_bans_handler(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<BanListParams>,
+) -> Result<Json<PageView<BanView>>, ServerError> {
+    let (bans, total) = list_bans(&state.db, params.all, params.offset, params.limit).await?;
+    let items = bans.iter().map(BanView::from).collect();
+    Ok(Json(PageView { items, total, offset: params.offset, limit: params.limit }))
+}
+
+pub async fn get_ban_handler(
+    State(state): State<AppState>,
+    Path(ban_id): Path<i32>,
+) -> Result<Json<BanView>, ServerError> {
+    let ban = find_ban(&state, ban_id).await?;
+    Ok(Json(BanView::from(&ban)))
+}
+
+pub async fn create_ban_handler(
+    ClientAddr(client_ip): ClientAddr,
+    State(state): State<AppState>,
+    ValidatedJson(params): ValidatedJson<BanParams>,
+) -> Result<Json<BanView>, ServerError> {
+    let target = ban_target(&params);
+    // the params validation keeps expires_in_secs well within range of an i64
+    let expires_at = params.expires_in_secs.map(|secs| Utc::now() + Duration::seconds(secs as i64));
+    let actor = format!("admin '{client_ip}'");
+    let issued_by = params.issued_by.as_deref().unwrap_or(&actor);
+    let ban = create_ban(&state.db, &target, params.realm.as_deref(), &params.reason, issued_by, expires_at).await?;
+    // the next check reloads the bans in force, this one included
+    state.cache.bans.invalidate_all();
+    tracing::info!("{actor} banned {target} (ban {}): {}", ban.id, ban.reason);
+    Ok(Json(BanView::from(&ban)))
+}
+
+pub async fn lift_ban_handler(
+    ClientAddr(client_ip): ClientAddr,
+    State(state): State<AppState>,
+    Path(ban_id): Path<i32>,
+) -> Result<Json<BanView>, ServerError> {
+    let ban = find_ban(&state, ban_id).await?;
+    if !is_active(&ban, Utc::now()) {
+        return Err(ServerError::Conflict(format!("ban {ban_id} has already been lifted or has expired")));
+    }
+    let actor = format!("admin '{client_ip}'");
+    let ban = lift_ban(&state.db, &ban, &actor).await?;
+    state.cache.bans.invalidate_all();
+    tracing::info!("{actor} lifted ban {ban_id} on {} '{}'", ban.kind, ban.target);
+    Ok(Json(BanView::from(&ban)))
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+
+use super::super::client_addr::ClientAddr;
+use super::super::errors::ServerError;
+use super::super::profile_server::history::{
+    diff_accounts, get_account_history, list_account_history, restore_account_history,
+    snapshot_account,
+};
+use super::super::profile_server::util::{get_account_from_db, get_player_from_db, make_account_json};
+use super::super::state::AppState;
+use super::super::validated_query::ValidatedQuery;
+use super::params::DiffParams;
+use super::realms::find_realm;
+use super::views::{AccountDiffView, AccountVersionView, AccountVersionsView, AccountView, PlayerView};
+use entity::{AccountHistoryModel, PlayerModel, RealmModel};
+
+async fn find_version(
+    state: &AppState,
+    realm: &RealmModel,
+    player_hash: i64,
+    history_id: i32,
+) -> Result<AccountHistoryModel, ServerError> {
+    get_account_history(&state.db, realm.id, player_hash, history_id)
+        .await?
+        .ok_or_else(|| {
+            ServerError::NotFound(format!("version {history_id} of account ('{}', {player_hash})", realm.name))
+        })
+}
+
+async fn find_player(state: &AppState, player_hash: i64) -> Result<PlayerModel, ServerError> {
+    get_player_from_db(&state.db, player_hash)
+        .await?
+        .ok_or_else(|| ServerError::NotFound(format!("player [hash:{player_hash}]")))
+}
+
+pub async fn list_history_handler(
+    State(state): State<AppState>,
+    Path((realm_name, player_hash)): Path<(String, i64)>,
+) -> Result<Json<AccountVersionsView>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let versions = list_account_history(&state.db, realm.id, player_hash).await?;
+    Ok(Json(AccountVersionsView {
+        realm: realm.name,
+        hash: player_hash,
+        versions: versions.iter().map(Into::into).collect(),
+    }))
+}
+
+pub async fn get_history_handler(
+    State(state): State<AppState>,
+    Path((realm_name, player_hash, history_id)): Path<(String, i64, i32)>,
+) -> Result<Json<AccountVersionView>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let history = find_version(&state, &realm, player_hash, history_id).await?;
+    let player = Arc::new(find_player(&state, player_hash).await?);
+    let account = Arc::new(snapshot_account(&history)?);
+    Ok(Json(AccountVersionView {
+        version: (&history).into(),
+        account: make_account_json(&player, &account)?,
+    }))
+}
+
+pub async fn diff_history_handler(
+    State(state): State<AppState>,
+    Path((realm_name, player_hash, history_id)): Path<(String, i64, i32)>,
+    ValidatedQuery(params): ValidatedQuery<DiffParams>,
+) -> Result<Json<AccountDiffView>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let from = snapshot_account(&find_version(&state, &realm, player_hash, history_id).await?)?;
+    // without a version to compare against, compare against the account as it is now
+    let to = match params.against {
+        Some(against) => snapshot_account(&find_version(&state, &realm, player_hash, against).await?)?,
+        None => get_account_from_db(&state.db, realm.id, player_hash)
+            .await?
+            .ok_or_else(|| ServerError::NotFound(format!("account ('{}', {player_hash})", realm.name)))?,
+    };
+    Ok(Json(AccountDiffView {
+        from: history_id,
+        to: params.against,
+        changes: diff_accounts(&from, &to)?,
+    }))
+}
+
+pub async fn restore_history_handler(
+    ClientAddr(client_ip): ClientAddr,
+    State(state): State<AppState>,
+    Path((realm_name, player_hash, history_id)): Path<(String, i64, i32)>,
+) -> Result<Json<AccountView>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    let history = find_version(&state, &realm, player_hash, history_id).await?;
+    let player = Arc::new(find_player(&state, player_hash).await?);
+    let account = restore_account_history(&state.db, &history, Some(client_ip), &state.config()).await?;
+    state.cache.invalidate_account(realm.id, player_hash).await;
+    tracing::info!(
+        "admin '{client_ip}' restored account ('{}', '{}') to version {history_id}",
+        realm.name,
+        player.username
+    );
+    let account = Arc::new(account);
+    Ok(Json(AccountView {
+        realm: realm.name.to_owned(),
+        player: PlayerView::from(player.as_ref()),
+        account: make_account_json(&player, &account)?,
+    }))
+}
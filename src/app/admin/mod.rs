@@ -0,0 +1,57 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+
+use super::state::AppState;
+
+pub mod accounts;
+pub mod anomalies;
+pub mod auth;
+pub mod bans;
+pub mod history;
+pub(super) mod params;
+pub mod players;
+pub mod realms;
+pub(super) mod views;
+
+pub fn admin_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/realms", get(realms::list_realms_handler))
+        .route("/realms/:realm", get(realms::get_realm_handler))
+        .route("/realms/:realm/accounts", get(accounts::list_accounts_handler))
+        .route(
+            "/realms/:realm/accounts/:hash",
+            get(accounts::get_account_handler).delete(accounts::delete_account_handler),
+        )
+        .route(
+            "/realms/:realm/accounts/:hash/history",
+            get(history::list_history_handler),
+        )
+        .route(
+            "/realms/:realm/accounts/:hash/history/:version",
+            get(history::get_history_handler),
+        )
+        .route(
+            "/realms/:realm/accounts/:hash/history/:version/diff",
+            get(history::diff_history_handler),
+        )
+        .route(
+            "/realms/:realm/accounts/:hash/history/:version/restore",
+            post(history::restore_history_handler),
+        )
+        .route("/players", get(players::list_players_handler))
+        .route(
+            "/players/:hash",
+            get(players::get_player_handler).delete(players::delete_player_handler),
+        )
+        .route("/anomalies", get(anomalies::list_anomalies_handler))
+        .route("/anomalies/:id", get(anomalies::get_anomaly_handler))
+        .route("/anomalies/:id/release", post(anomalies::release_anomaly_handler))
+        .route("/anomalies/:id/dismiss", post(anomalies::dismiss_anomaly_handler))
+        .route("/bans", get(bans::list_bans_handler).post(bans::create_ban_handler))
+        .route("/bans/:id", get(bans::get_ban_handler))
+        .route("/bans/:id/lift", post(bans::lift_ban_handler))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_admin_token))
+}
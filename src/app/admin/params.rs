@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+
+use super::super::profile_server::bans::BanTarget;
+
+fn default_limit() -> u64 {
+    50
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PageParams {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default = "default_limit")]
+    #[validate(range(min = 1, max = 500))]
+    pub limit: u64,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PlayerListParams {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default = "default_limit")]
+    #[validate(range(min = 1, max = 500))]
+    pub limit: u64,
+    #[validate(length(min = 1, max = 32))]
+    pub username: Option<String>,
+    pub hash: Option<i64>,
+    pub sid: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DiffParams {
+    pub against: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AnomalyListParams {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default = "default_limit")]
+    #[validate(range(min = 1, max = 500))]
+    pub limit: u64,
+    #[validate(length(min = 1, max = 32))]
+    pub realm: Option<String>,
+    // resolved anomalies are left out unless asked for
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct BanListParams {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default = "default_limit")]
+    #[validate(range(min = 1, max = 500))]
+    pub limit: u64,
+    // lifted and expired bans are left out unless asked for
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_ban_target"))]
+pub struct BanParams {
+    pub sid: Option<i64>,
+    #[validate(length(min = 1, max = 32))]
+    pub username: Option<String>,
+    pub hash: Option<i64>,
+    #[validate(custom = "validate_ban_ip")]
+    pub ip: Option<String>,
+    #[validate(length(min = 1, max = 32))]
+    pub realm: Option<String>,
+    #[validate(length(min = 1, max = 1024))]
+    pub reason: String,
+    #[validate(length(min = 1, max = 64))]
+    pub issued_by: Option<String>,
+    // ten years at most, past that a ban might as well not expire
+    #[validate(range(min = 1, max = 315360000))]
+    pub expires_in_secs: Option<u64>,
+}
+
+fn validate_ban_target(params: &BanParams) -> Result<(), ValidationError> {
+    let targets = [params.sid.is_some(), params.username.is_some(), params.hash.is_some(), params.ip.is_some()];
+    match targets.iter().filter(|target| **target).count() {
+        1 => Ok(()),
+        _ => Err(ValidationError::new("exactly one of sid, username, hash or ip is required")),
+    }
+}
+
+fn validate_ban_ip(ip: &str) -> Result<(), ValidationError> {
+    match BanTarget::ip(ip) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("must be an ip address or cidr block")),
+    }
+}
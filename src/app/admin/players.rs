@@ -0,0 +1,74 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use super::super::errors::ServerError;
+use super::super::profile_server::util::{delete_player_from_db, get_player_from_db};
+use super::super::state::AppState;
+use super::super::validated_query::ValidatedQuery;
+use super::params::PlayerListParams;
+use super::views::{PageView, PlayerDetailView, PlayerView};
+use entity::{Account, AccountColumn, Player, PlayerColumn, PlayerModel, Realm};
+
+async fn find_player(state: &AppState, player_hash: i64) -> Result<PlayerModel, ServerError> {
+    get_player_from_db(&state.db, player_hash)
+        .await?
+        .ok_or_else(|| ServerError::NotFound(format!("player [hash:{player_hash}]")))
+}
+
+pub async fn list_players_handler(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<PlayerListParams>,
+) -> Result<Json<PageView<PlayerView>>, ServerError> {
+    let mut query = Player::find().order_by_asc(PlayerColumn::Username);
+    if let Some(username) = &params.username {
+        query = query.filter(PlayerColumn::Username.eq(username.as_str()));
+    }
+    if let Some(hash) = params.hash {
+        query = query.filter(PlayerColumn::Hash.eq(hash));
+    }
+    if let Some(sid) = params.sid {
+        query = query.filter(PlayerColumn::Sid.eq(sid));
+    }
+    let total = query.clone().count(&state.db).await?;
+    let players = query
+        .offset(params.offset)
+        .limit(params.limit)
+        .all(&state.db)
+        .await?;
+    let items = players.iter().map(PlayerView::from).collect();
+    Ok(Json(PageView { items, total, offset: params.offset, limit: params.limit }))
+}
+
+pub async fn get_player_handler(
+    State(state): State<AppState>,
+    Path(player_hash): Path<i64>,
+) -> Result<Json<PlayerDetailView>, ServerError> {
+    let player = find_player(&state, player_hash).await?;
+    let realms = Account::find()
+        .filter(AccountColumn::Hash.eq(player.hash))
+        .find_also_related(Realm)
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .filter_map(|(_, realm)| realm.map(|realm| realm.name))
+        .collect();
+    Ok(Json(PlayerDetailView { player: PlayerView::from(&player), realms }))
+}
+
+pub async fn delete_player_handler(
+    State(state): State<AppState>,
+    Path(player_hash): Path<i64>,
+) -> Result<StatusCode, ServerError> {
+    let player = find_player(&state, player_hash).await?;
+    let realm_ids = delete_player_from_db(&state.db, player.hash).await?;
+    state.cache.invalidate_player(player.hash, &realm_ids).await;
+    tracing::info!(
+        "admin deleted player '{}' [hash:{}] and {} account(s)",
+        player.username,
+        player.hash,
+        realm_ids.len()
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,51 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use super::super::errors::ServerError;
+use super::super::profile_server::util::get_realm_from_db;
+use super::super::state::AppState;
+use super::super::validated_query::ValidatedQuery;
+use super::params::PageParams;
+use super::views::{PageView, RealmView};
+use entity::{Account, AccountColumn, Realm, RealmColumn, RealmModel};
+
+pub(super) async fn find_realm(state: &AppState, realm_name: &str) -> Result<RealmModel, ServerError> {
+    get_realm_from_db(&state.db, realm_name)
+        .await?
+        .ok_or_else(|| ServerError::NotFound(format!("realm '{realm_name}'")))
+}
+
+async fn make_realm_view(state: &AppState, realm: &RealmModel) -> Result<RealmView, ServerError> {
+    let accounts = Account::find()
+        .filter(AccountColumn::RealmId.eq(realm.id))
+        .count(&state.db)
+        .await?;
+    Ok(RealmView::new(realm, accounts))
+}
+
+pub async fn list_realms_handler(
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<PageParams>,
+) -> Result<Json<PageView<RealmView>>, ServerError> {
+    let query = Realm::find().order_by_asc(RealmColumn::Id);
+    let total = query.clone().count(&state.db).await?;
+    let realms = query
+        .offset(params.offset)
+        .limit(params.limit)
+        .all(&state.db)
+        .await?;
+    let mut items = Vec::with_capacity(realms.len());
+    for realm in realms.iter() {
+        items.push(make_realm_view(&state, realm).await?);
+    }
+    Ok(Json(PageView { items, total, offset: params.offset, limit: params.limit }))
+}
+
+pub async fn get_realm_handler(
+    State(state): State<AppState>,
+    Path(realm_name): Path<String>,
+) -> Result<Json<RealmView>, ServerError> {
+    let realm = find_realm(&state, &realm_name).await?;
+    Ok(Json(make_realm_view(&state, &realm).await?))
+}
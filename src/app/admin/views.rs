@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::super::profile_server::anomaly::{anomaly_hits, AnomalyHit};
+use super::super::profile_server::bans::is_active;
+use super::super::profile_server::errors::ProfileServerError;
+use super::super::profile_server::history::AccountChange;
+use entity::{AccountAnomalyModel, AccountHistoryModel, AccountModel, BanModel, PlayerModel, RealmModel};
+
+#[derive(Serialize)]
+pub struct PageView<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+}
+
+// digests and rids are credentials, so they are never handed out
+#[derive(Serialize)]
+pub struct RealmView {
+    pub id: i32,
+    pub name: String,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub previous_digest_expires_at: Option<DateTime<Utc>>,
+    pub accounts: u64,
+}
+
+impl RealmView {
+    pub fn new(realm: &RealmModel, accounts: u64) -> Self {
+        Self {
+            id: realm.id,
+            name: realm.name.to_owned(),
+            created_by: realm.created_by.to_owned(),
+            created_at: realm.created_at,
+            previous_digest_expires_at: realm
+                .previous_digest
+                .as_ref()
+                .and(realm.previous_digest_expires_at),
+            accounts,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlayerView {
+    pub hash: i64,
+    pub username: String,
+    pub sid: i64,
+}
+
+impl From<&PlayerModel> for PlayerView {
+    fn from(player: &PlayerModel) -> Self {
+        Self {
+            hash: player.hash,
+            username: player.username.to_owned(),
+            sid: player.sid,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PlayerDetailView {
+    #[serde(flatten)]
+    pub player: PlayerView,
+    pub realms: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct AccountSummaryView {
+    pub realm_id: i32,
+    pub hash: i64,
+    pub username: Option<String>,
+    pub squad_tag: String,
+    pub authority: f64,
+    pub job_points: f64,
+    pub kills: i32,
+    pub deaths: i32,
+    pub time_played: i32,
+}
+
+impl AccountSummaryView {
+    pub fn new(account: &AccountModel, player: Option<&PlayerModel>) -> Self {
+        Self {
+            realm_id: account.realm_id,
+            hash: account.hash,
+            username: player.map(|player| player.username.to_owned()),
+            squad_tag: account.squad_tag.to_owned(),
+            authority: account.authority,
+            job_points: account.job_points,
+            kills: account.kills,
+            deaths: account.deaths,
+            time_played: account.time_played,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AccountView {
+    pub realm: String,
+    pub player: PlayerView,
+    // the account as the game server would see it, with the json blobs decoded
+    pub account: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct AccountVersionSummaryView {
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub source: String,
+    pub source_ip: Option<String>,
+}
+
+impl From<&AccountHistoryModel> for AccountVersionSummaryView {
+    fn from(history: &AccountHistoryModel) -> Self {
+        Self {
+            version: history.id,
+            created_at: history.created_at,
+            source: history.source.to_owned(),
+            source_ip: history.source_ip.to_owned(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AccountVersionsView {
+    pub realm: String,
+    pub hash: i64,
+    pub versions: Vec<AccountVersionSummaryView>,
+}
+
+#[derive(Serialize)]
+pub struct AccountVersionView {
+    #[serde(flatten)]
+    pub version: AccountVersionSummaryView,
+    pub account: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct AccountDiffView {
+    pub from: i32,
+    // no version means the account as it is now
+    pub to: Option<i32>,
+    pub changes: Vec<AccountChange>,
+}
+
+#[derive(Serialize)]
+pub struct AnomalyView {
+    pub id: i32,
+    pub realm_id: i32,
+    pub hash: i64,
+    pub created_at: DateTime<Utc>,
+    pub action: String,
+    pub hits: Vec<AnomalyHit>,
+    pub source_ip: Option<String>,
+    pub resolution: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolved_by: Option<String>,
+}
+
+impl AnomalyView {
+    pub fn new(anomaly: &AccountAnomalyModel) -> Result<Self, ProfileServerError> {
+        Ok(Self {
+            id: anomaly.id,
+            realm_id: anomaly.realm_id,
+            hash: anomaly.hash,
+            created_at: anomaly.created_at,
+            action: anomaly.action.to_owned(),
+            hits: anomaly_hits(anomaly)?,
+            source_ip: anomaly.source_ip.to_owned(),
+            resolution: anomaly.resolution.to_owned(),
+            resolved_at: anomaly.resolved_at,
+            resolved_by: anomaly.resolved_by.to_owned(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct AnomalyDetailView {
+    #[serde(flatten)]
+    pub anomaly: AnomalyView,
+    // the incoming save that tripped the rules
+    pub account: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct BanView {
+    pub id: i32,
+    pub kind: String,
+    pub target: String,
+    pub realm: Option<String>,
+    pub reason: String,
+    pub issued_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub lifted_at: Option<DateTime<Utc>>,
+    pub lifted_by: Option<String>,
+    pub active: bool,
+}
+
+impl From<&BanModel> for BanView {
+    fn from(ban: &BanModel) -> Self {
+        Self {
+            id: ban.id,
+            kind: ban.kind.to_owned(),
+            target: ban.target.to_owned(),
+            realm: ban.realm.to_owned(),
+            reason: ban.reason.to_owned(),
+            issued_by: ban.issued_by.to_owned(),
+            created_at: ban.created_at,
+            expires_at: ban.expires_at,
+            lifted_at: ban.lifted_at,
+            lifted_by: ban.lifted_by.to_owned(),
+            active: is_active(ban, Utc::now()),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use quick_xml::{events::Event, reader::Reader};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ItemClass {
+    Weapon,
+    Projectile,
+    CarryItem,
+}
+
+impl ItemClass {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "weapon" => Some(ItemClass::Weapon),
+            "projectile" => Some(ItemClass::Projectile),
+            "carry_item" => Some(ItemClass::CarryItem),
+            _ => None,
+        }
+    }
+
+    // the class attribute of backpack and stash item groups
+    pub fn from_store_class(class: u8) -> Option<Self> {
+        match class {
+            0 => Some(ItemClass::Weapon),
+            1 => Some(ItemClass::Projectile),
+            3 => Some(ItemClass::CarryItem),
+            _ => None,
+        }
+    }
+
+    // the loadout slots: primary and secondary weapons, grenades and armour
+    pub fn from_slot(slot: u8) -> Option<Self> {
+        match slot {
+            0 | 1 => Some(ItemClass::Weapon),
+            2 => Some(ItemClass::Projectile),
+            4 => Some(ItemClass::CarryItem),
+            _ => None,
+        }
+    }
+
+    fn element_name(&self) -> &'static str {
+        match self {
+            ItemClass::Weapon => "weapon",
+            ItemClass::Projectile => "projectile",
+            ItemClass::CarryItem => "carry_item",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CatalogToml {
+    weapons: Vec<String>,
+    projectiles: Vec<String>,
+    carry_items: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ItemCatalog {
+    items: HashMap<String, ItemClass>,
+}
+
+impl ItemCatalog {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut catalog = ItemCatalog::default();
+        if path.is_dir() {
+            catalog.add_dir(path)?;
+        } else {
+            catalog.add_toml(path)?;
+        }
+        if catalog.items.is_empty() {
+            anyhow::bail!("item catalog '{}' has no items in it", path.display());
+        }
+        Ok(catalog)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn class_of(&self, key: &str) -> Option<ItemClass> {
+        self.items.get(key).copied()
+    }
+
+    fn add_toml(&mut self, path: &Path) -> anyhow::Result<()> {
+        if !path.is_file() {
+            anyhow::bail!("item catalog '{}' is neither a directory nor a file", path.display());
+        }
+        let catalog_toml: CatalogToml = Figment::from(Toml::file(path))
+            .extract()
+            .with_context(|| format!("failed to read item catalog '{}'", path.display()))?;
+        let lists = [
+            (catalog_toml.weapons, ItemClass::Weapon),
+            (catalog_toml.projectiles, ItemClass::Projectile),
+            (catalog_toml.carry_items, ItemClass::CarryItem),
+        ];
+        for (keys, class) in lists {
+            for key in keys {
+                self.items.insert(key, class);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let entries = fs::read_dir(dir).with_context(|| format!("failed to read directory '{}'", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.add_dir(&path)?;
+                continue;
+            }
+            let Some(class) = path.extension().and_then(|ext| ext.to_str()).and_then(ItemClass::from_extension) else {
+                continue;
+            };
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let xml = fs::read_to_string(&path).with_context(|| format!("failed to read '{}'", path.display()))?;
+            // a file can hold several items under their own keys, a lone item without a key goes by its file name
+            let keys = match item_keys(&xml, class) {
+                Ok(keys) if !keys.is_empty() => keys,
+                Ok(_) => vec![String::from(file_name)],
+                Err(err) => {
+                    tracing::warn!("failed to parse '{}', cataloguing it by file name: {err}", path.display());
+                    vec![String::from(file_name)]
+                }
+            };
+            for key in keys {
+                self.items.insert(key, class);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn item_keys(xml: &str, class: ItemClass) -> Result<Vec<String>, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut keys = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element) if element.name().as_ref() == class.element_name().as_bytes() => {
+                for attribute in element.attributes().flatten() {
+                    if attribute.key.as_ref() == b"key" {
+                        keys.push(attribute.unescape_value()?.into_owned());
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing::fixture_path;
+
+    #[test]
+    fn catalogs_load_from_item_directories() {
+        let catalog = ItemCatalog::load(&fixture_path("items")).unwrap();
+        // ak47.weapon has no key of its own and goes by its file name, readme.txt isn't an item at all
+        assert_eq!(catalog.len(), 6);
+        assert_eq!(catalog.class_of("ak47.weapon"), Some(ItemClass::Weapon));
+        assert_eq!(catalog.class_of("m16a4.weapon"), Some(ItemClass::Weapon));
+        assert_eq!(catalog.class_of("aug.weapon"), Some(ItemClass::Weapon));
+        assert_eq!(catalog.class_of("hand_grenade.projectile"), Some(ItemClass::Projectile));
+        assert_eq!(catalog.class_of("vest1.carry_item"), Some(ItemClass::CarryItem));
+        assert_eq!(catalog.class_of("vest2.carry_item"), Some(ItemClass::CarryItem));
+        assert_eq!(catalog.class_of("assault_rifles.weapon"), None);
+        assert_eq!(catalog.class_of("readme.txt"), None);
+    }
+
+    #[test]
+    fn catalogs_load_from_toml() {
+        let catalog = ItemCatalog::load(&fixture_path("items.toml")).unwrap();
+        assert_eq!(catalog.len(), 5);
+        assert_eq!(catalog.class_of("ak47.weapon"), Some(ItemClass::Weapon));
+        assert_eq!(catalog.class_of("hand_grenade.projectile"), Some(ItemClass::Projectile));
+        assert_eq!(catalog.class_of("vest2.carry_item"), Some(ItemClass::CarryItem));
+        // the class comes from the list an item is in, whatever its key says
+        assert_eq!(catalog.class_of("vest1.carry_item"), Some(ItemClass::Weapon));
+
+        assert!(ItemCatalog::load(&fixture_path("items/missing.toml")).is_err());
+        // a directory without any items in it
+        assert!(ItemCatalog::load(&fixture_path("vanilla")).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use quick_xml::{events::Event, reader::Reader, writer::Writer};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use super::super::profile_server::util::{
+    get_account_from_db, get_realm_from_db, make_account_json, make_account_xml,
+};
+use super::players::find_player;
+use super::{AccountFormat, Page};
+use entity::{Account, AccountColumn, Player};
+
+pub async fn list_accounts(db: &DatabaseConnection, realm_name: &str, page: &Page) -> anyhow::Result<()> {
+    let Some(realm) = get_realm_from_db(db, realm_name).await? else {
+        anyhow::bail!("realm '{realm_name}' does not exist");
+    };
+    let query = Account::find()
+        .filter(AccountColumn::RealmId.eq(realm.id))
+        .order_by_asc(AccountColumn::Hash);
+    let total = query.clone().count(db).await?;
+    let accounts = query
+        .find_also_related(Player)
+        .offset(page.offset)
+        .limit(page.limit)
+        .all(db)
+        .await?;
+    println!(
+        "{:>12}  {:<32} {:<6} {:>8} {:>8} {:>10} {:>8}",
+        "hash", "username", "tag", "xp", "kills", "deaths", "hours"
+    );
+    for (account, player) in accounts.iter() {
+        println!(
+            "{:>12}  {:<32} {:<6} {:>8.0} {:>8} {:>10} {:>8.1}",
+            account.hash,
+            player.as_ref().map(|player| player.username.as_str()).unwrap_or("?"),
+            account.squad_tag,
+            account.authority * 10000.0,
+            account.kills,
+            account.deaths,
+            account.time_played as f64 / 3600.0
+        );
+    }
+    println!("({} of {} accounts in realm '{}')", accounts.len(), total, realm.name);
+    Ok(())
+}
+
+pub async fn show_account(
+    db: &DatabaseConnection,
+    realm_name: &str,
+    player: &str,
+    format: AccountFormat,
+) -> anyhow::Result<()> {
+    let Some(realm) = get_realm_from_db(db, realm_name).await? else {
+        anyhow::bail!("realm '{realm_name}' does not exist");
+    };
+    let player = find_player(db, player).await?;
+    let Some(account) = get_account_from_db(db, realm.id, player.hash).await? else {
+        anyhow::bail!("player '{}' has no account in realm '{}'", player.username, realm.name);
+    };
+    let (player, account) = (Arc::new(player), Arc::new(account));
+    match format {
+        AccountFormat::Xml => print!("{}", pretty_xml(&make_account_xml(&player, &account)?)?),
+        AccountFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&make_account_json(&player, &account)?)?)
+        }
+    }
+    Ok(())
+}
+
+fn pretty_xml(xml: &str) -> anyhow::Result<String> {
+    // the game servers get the xml on one line, re-indent it for people
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+    let mut pretty = String::from_utf8(writer.into_inner().into_inner())?;
+    pretty.push('\n');
+    Ok(pretty)
+}
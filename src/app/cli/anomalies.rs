@@ -0,0 +1,77 @@
+use sea_orm::DatabaseConnection;
+
+use super::super::config::AppConfiguration;
+use super::super::profile_server::anomaly::{
+    anomaly_hits, dismiss_anomaly, get_anomaly, is_releasable, list_anomalies, release_anomaly,
+};
+use super::super::profile_server::util::get_realm_from_db;
+use super::{check_no_running_server, Page};
+use entity::AccountAnomalyModel;
+
+async fn find_anomaly(db: &DatabaseConnection, anomaly_id: i32) -> anyhow::Result<AccountAnomalyModel> {
+    match get_anomaly(db, anomaly_id).await? {
+        Some(anomaly) => Ok(anomaly),
+        None => anyhow::bail!("anomaly {anomaly_id} not found"),
+    }
+}
+
+pub async fn list(db: &DatabaseConnection, realm: Option<&str>, all: bool, page: &Page) -> anyhow::Result<()> {
+    let realm_id = match realm {
+        Some(realm_name) => match get_realm_from_db(db, realm_name).await? {
+            Some(realm) => Some(realm.id),
+            None => anyhow::bail!("realm '{realm_name}' does not exist"),
+        },
+        None => None,
+    };
+    let (anomalies, total) = list_anomalies(db, realm_id, all, page.offset, page.limit).await?;
+    println!(
+        "{:>6}  {:>5} {:>12}  {:<26} {:<11} {:<10} hits",
+        "id", "realm", "hash", "created", "action", "resolution"
+    );
+    for anomaly in anomalies.iter() {
+        println!(
+            "{:>6}  {:>5} {:>12}  {:<26} {:<11} {:<10} {}",
+            anomaly.id,
+            anomaly.realm_id,
+            anomaly.hash,
+            anomaly.created_at.format("%Y-%m-%dT%H:%M:%S%:z"),
+            anomaly.action,
+            anomaly.resolution.as_deref().unwrap_or("-"),
+            anomaly_hits(anomaly)?
+                .iter()
+                .map(|hit| format!("{}: {}", hit.rule, hit.detail))
+                .collect::<Vec<String>>()
+                .join("; ")
+        );
+    }
+    let kind = if all { "anomalies" } else { "unresolved anomalies" };
+    println!("({} of {} {kind})", anomalies.len(), total);
+    Ok(())
+}
+
+pub async fn release(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    anomaly_id: i32,
+    force: bool,
+) -> anyhow::Result<()> {
+    let anomaly = find_anomaly(db, anomaly_id).await?;
+    if !is_releasable(&anomaly) {
+        anyhow::bail!("anomaly {anomaly_id} is not a quarantined save awaiting review");
+    }
+    let admin_api = format!("POST /admin/anomalies/{anomaly_id}/release");
+    check_no_running_server(app_config, force, Some(&admin_api)).await?;
+    release_anomaly(db, &anomaly, "cli", app_config).await?;
+    println!("released quarantined save {anomaly_id} into account [{},{}]", anomaly.realm_id, anomaly.hash);
+    Ok(())
+}
+
+pub async fn dismiss(db: &DatabaseConnection, anomaly_id: i32) -> anyhow::Result<()> {
+    let anomaly = find_anomaly(db, anomaly_id).await?;
+    if anomaly.resolution.is_some() {
+        anyhow::bail!("anomaly {anomaly_id} has already been resolved");
+    }
+    dismiss_anomaly(db, &anomaly, "cli").await?;
+    println!("dismissed anomaly {anomaly_id}");
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use chrono::{Duration, Utc};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+use super::super::profile_server::bans::{create_ban, get_ban, is_active, lift_ban, list_bans, BanTarget};
+use super::{BanTargetArgs, Page};
+use entity::{Player, PlayerColumn};
+
+async fn ban_target(db: &DatabaseConnection, target: &BanTargetArgs) -> anyhow::Result<BanTarget> {
+    if let Some(sid) = target.sid {
+        return Ok(BanTarget::Sid(sid));
+    }
+    if let Some(ip) = &target.ip {
+        return BanTarget::ip(ip).map_err(anyhow::Error::msg);
+    }
+    let Some(player) = &target.player else {
+        anyhow::bail!("one of --sid, --player or --ip is required");
+    };
+    // usernames come first, then hashes, and a player that has never enlisted is banned by their username's hash
+    if let Some(player) = Player::find().filter(PlayerColumn::Username.eq(player.as_str())).one(db).await? {
+        return Ok(BanTarget::Hash(player.hash));
+    }
+    match player.parse::<i64>() {
+        Ok(hash) => Ok(BanTarget::Hash(hash)),
+        Err(_) => Ok(BanTarget::username(player)),
+    }
+}
+
+pub async fn list(db: &DatabaseConnection, all: bool, page: &Page) -> anyhow::Result<()> {
+    let (bans, total) = list_bans(db, all, page.offset, page.limit).await?;
+    let now = Utc::now();
+    println!(
+        "{:>6}  {:<4} {:<20} {:<12} {:<26} {:<26} {:<8} {:<16} reason",
+        "id", "kind", "target", "realm", "created", "expires", "state", "issued by"
+    );
+    for ban in bans.iter() {
+        let state = match (ban.lifted_at, is_active(ban, now)) {
+            (Some(_), _) => "lifted",
+            (None, true) => "active",
+            (None, false) => "expired",
+        };
+        println!(
+            "{:>6}  {:<4} {:<20} {:<12} {:<26} {:<26} {:<8} {:<16} {}",
+            ban.id,
+            ban.kind,
+            ban.target,
+            ban.realm.as_deref().unwrap_or("*"),
+            ban.created_at.format("%Y-%m-%dT%H:%M:%S%:z"),
+            ban.expires_at
+                .map(|expires_at| expires_at.format("%Y-%m-%dT%H:%M:%S%:z").to_string())
+                .unwrap_or_else(|| String::from("never")),
+            state,
+            ban.issued_by,
+            ban.reason
+        );
+    }
+    let kind = if all { "bans" } else { "bans in force" };
+    println!("({} of {} {kind})", bans.len(), total);
+    Ok(())
+}
+
+pub async fn ban(
+    db: &DatabaseConnection,
+    target: &BanTargetArgs,
+    realm: Option<&str>,
+    reason: &str,
+    issued_by: &str,
+    expires_in_secs: Option<u64>,
+) -> anyhow::Result<()> {
+    let target = ban_target(db, target).await?;
+    let expires_at = match expires_in_secs {
+        Some(secs) => Some(Utc::now() + Duration::seconds(i64::try_from(secs)?)),
+        None => None,
+    };
+    let ban = create_ban(db, &target, realm, reason, issued_by, expires_at).await?;
+    let scope = match realm {
+        Some(realm) => format!("realm '{realm}'"),
+        None => String::from("every realm"),
+    };
+    println!(
+        "banned {target} in {scope} as ban {}, a running profile server picks it up within 30 seconds",
+        ban.id
+    );
+    Ok(())
+}
+
+pub async fn unban(db: &DatabaseConnection, ban_id: i32, lifted_by: &str) -> anyhow::Result<()> {
+    let Some(ban) = get_ban(db, ban_id).await? else {
+        anyhow::bail!("ban {ban_id} not found");
+    };
+    if !is_active(&ban, Utc::now()) {
+        anyhow::bail!("ban {ban_id} has already been lifted or has expired");
+    }
+    lift_ban(db, &ban, lifted_by).await?;
+    println!("lifted ban {ban_id} on {} '{}'", ban.kind, ban.target);
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use chrono::{TimeZone, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection, EntityTrait, IntoActiveModel,
+    PaginatorTrait, QueryOrder, Statement, TransactionTrait,
+};
+
+use migration::{Migrator, MigratorTrait};
+
+use super::super::config::{AppConfiguration, ItemStorage};
+use super::super::profile_server::account_items::move_account_items;
+use super::super::profile_server::json::{upgrade_account_blobs, SCHEMA_VERSION};
+use super::{check_no_running_server, MigrateAction};
+use entity::{Account, AccountColumn};
+
+pub async fn migrate(db: &DatabaseConnection, action: MigrateAction) -> anyhow::Result<()> {
+    match action {
+        MigrateAction::Up { steps } => Migrator::up(db, steps).await?,
+        MigrateAction::Down { steps } => Migrator::down(db, Some(steps)).await?,
+        MigrateAction::Status => {}
+    }
+    let applied = Migrator::get_migration_models(db).await?;
+    for migration in Migrator::migrations() {
+        let applied_at = applied
+            .iter()
+            .find(|model| model.version == migration.name())
+            .map(|model| model.applied_at);
+        match applied_at {
+            Some(applied_at) => {
+                let applied_at = Utc.timestamp_opt(applied_at, 0).single().unwrap_or_default();
+                println!("applied  {} (at {})", migration.name(), applied_at.to_rfc3339())
+            }
+            None => println!("pending  {}", migration.name()),
+        }
+    }
+    Ok(())
+}
+
+pub async fn vacuum(db: &DatabaseConnection) -> anyhow::Result<()> {
+    // there is no orm equivalent, but both sqlite and postgres understand a bare VACUUM
+    let backend = db.get_database_backend();
+    db.execute(Statement::from_string(backend, String::from("VACUUM")))
+        .await?;
+    println!("vacuumed the db");
+    Ok(())
+}
+
+pub async fn upgrade_json(db: &DatabaseConnection, dry_run: bool) -> anyhow::Result<()> {
+    let (mut scanned, mut upgraded) = (0, 0);
+    // a dry run does all the same work in a transaction that is then thrown away
+    let txn = db.begin().await?;
+    let mut pages = Account::find()
+        .order_by_asc(AccountColumn::RealmId)
+        .order_by_asc(AccountColumn::Hash)
+        .paginate(&txn, 256);
+    while let Some(accounts) = pages.fetch_and_next().await? {
+        for mut account in accounts {
+            scanned += 1;
+            let mut active_account = account.clone().into_active_model();
+            if !upgrade_account_blobs(&mut account)? {
+                continue;
+            }
+            // only the json columns are written so that nothing else about the account is touched
+            active_account.loadout = ActiveValue::Set(account.loadout);
+            active_account.backpack = ActiveValue::Set(account.backpack);
+            active_account.stash = ActiveValue::Set(account.stash);
+            active_account.kill_combos = ActiveValue::Set(account.kill_combos);
+            active_account.criteria_monitors = ActiveValue::Set(account.criteria_monitors);
+            active_account.extras = ActiveValue::Set(account.extras);
+            active_account.update(&txn).await?;
+            upgraded += 1;
+        }
+    }
+    match dry_run {
+        true => txn.rollback().await?,
+        false => txn.commit().await?,
+    }
+    println!(
+        "{}{upgraded} of {scanned} account(s) upgraded to json schema v{SCHEMA_VERSION}",
+        if dry_run { "dry run: " } else { "" }
+    );
+    Ok(())
+}
+
+pub async fn move_items(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    storage: ItemStorage,
+    dry_run: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    if !dry_run {
+        check_no_running_server(app_config, force, None).await?;
+    }
+    let (setting, storage_name) = match storage {
+        ItemStorage::Json => ("json", "json columns"),
+        ItemStorage::Table => ("table", "account_item rows"),
+    };
+    // a dry run does all the same work in a transaction that is then thrown away
+    let txn = db.begin().await?;
+    let moved = move_account_items(&txn, storage).await?;
+    match dry_run {
+        true => txn.rollback().await?,
+        false => txn.commit().await?,
+    }
+    println!(
+        "{}moved the items of {moved} account(s) to {storage_name}",
+        if dry_run { "dry run: " } else { "" }
+    );
+    if app_config.ps_item_storage != storage {
+        println!("set ps_item_storage = \"{setting}\" before starting the profile server again");
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use sea_orm::DatabaseConnection;
+
+use super::super::config::AppConfiguration;
+use super::super::profile_server::history::{
+    diff_accounts, get_account_history, list_account_history, restore_account_history,
+    snapshot_account,
+};
+use super::super::profile_server::util::{get_account_from_db, get_realm_from_db};
+use super::check_no_running_server;
+use super::players::find_player;
+use entity::{AccountHistoryModel, PlayerModel, RealmModel};
+
+async fn find_realm_and_player(
+    db: &DatabaseConnection,
+    realm_name: &str,
+    player: &str,
+) -> anyhow::Result<(RealmModel, PlayerModel)> {
+    let Some(realm) = get_realm_from_db(db, realm_name).await? else {
+        anyhow::bail!("realm '{realm_name}' does not exist");
+    };
+    let player = find_player(db, player).await?;
+    Ok((realm, player))
+}
+
+async fn find_version(
+    db: &DatabaseConnection,
+    realm: &RealmModel,
+    player: &PlayerModel,
+    history_id: i32,
+) -> anyhow::Result<AccountHistoryModel> {
+    match get_account_history(db, realm.id, player.hash, history_id).await? {
+        Some(history) => Ok(history),
+        None => anyhow::bail!(
+            "version {history_id} of account ('{}', '{}') not found",
+            realm.name,
+            player.username
+        ),
+    }
+}
+
+pub async fn list_history(db: &DatabaseConnection, realm_name: &str, player: &str) -> anyhow::Result<()> {
+    let (realm, player) = find_realm_and_player(db, realm_name, player).await?;
+    let versions = list_account_history(db, realm.id, player.hash).await?;
+    println!("{:>8}  {:<34} {:<12} source ip", "version", "created", "source");
+    for version in versions.iter() {
+        println!(
+            "{:>8}  {:<34} {:<12} {}",
+            version.id,
+            version.created_at.to_rfc3339(),
+            version.source,
+            version.source_ip.as_deref().unwrap_or("-")
+        );
+    }
+    println!("({} versions of account ('{}', '{}'))", versions.len(), realm.name, player.username);
+    Ok(())
+}
+
+pub async fn diff_history(
+    db: &DatabaseConnection,
+    realm_name: &str,
+    player: &str,
+    history_id: i32,
+    against: Option<i32>,
+) -> anyhow::Result<()> {
+    let (realm, player) = find_realm_and_player(db, realm_name, player).await?;
+    let from = snapshot_account(&find_version(db, &realm, &player, history_id).await?)?;
+    // without a version to compare against, compare against the account as it is now
+    let (to, to_name) = match against {
+        Some(against) => (
+            snapshot_account(&find_version(db, &realm, &player, against).await?)?,
+            format!("version {against}"),
+        ),
+        None => match get_account_from_db(db, realm.id, player.hash).await? {
+            Some(account) => (account, String::from("the current account")),
+            None => anyhow::bail!("player '{}' has no account in realm '{}'", player.username, realm.name),
+        },
+    };
+    let changes = diff_accounts(&from, &to)?;
+    for change in changes.iter() {
+        println!("{}: {} -> {}", change.path, change.from, change.to);
+    }
+    println!("({} change(s) from version {history_id} to {to_name})", changes.len());
+    Ok(())
+}
+
+pub async fn restore_history(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    realm_name: &str,
+    player: &str,
+    history_id: i32,
+    force: bool,
+) -> anyhow::Result<()> {
+    let (realm, player) = find_realm_and_player(db, realm_name, player).await?;
+    let history = find_version(db, &realm, &player, history_id).await?;
+    let admin_api = format!(
+        "POST /admin/realms/{}/accounts/{}/history/{history_id}/restore",
+        realm.name, player.hash
+    );
+    check_no_running_server(app_config, force, Some(&admin_api)).await?;
+    restore_account_history(db, &history, None, app_config).await?;
+    println!("restored account ('{}', '{}') to version {history_id}", realm.name, player.username);
+    Ok(())
+}
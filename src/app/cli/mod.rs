@@ -0,0 +1,455 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use sea_orm::DatabaseConnection;
+use tokio::net::TcpStream;
+
+use super::config::{AppConfiguration, ItemStorage, MAX_DIGEST_GRACE_SECS};
+
+pub mod accounts;
+pub mod anomalies;
+pub mod bans;
+pub mod db;
+pub mod history;
+pub mod players;
+pub mod profiles;
+pub mod realms;
+pub mod varvaytya;
+
+#[derive(Debug, Parser)]
+#[command(name = "marshalrwr", version, about = "A bespoke profile server for Running with Rifles")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the profile server (the default)
+    Serve,
+    /// List the realms in the db
+    Realms,
+    /// List the players in the db
+    Players {
+        /// Only list players with an account in this realm
+        #[arg(long)]
+        realm: Option<String>,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// List the accounts in a realm
+    Accounts {
+        /// The realm name
+        realm: String,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Show a player's account in a realm
+    Account {
+        /// The realm name
+        realm: String,
+        /// The player's username or hash
+        player: String,
+        #[arg(long, value_enum, default_value_t = AccountFormat::Xml)]
+        format: AccountFormat,
+    },
+    /// List the saved versions of a player's account in a realm
+    History {
+        /// The realm name
+        realm: String,
+        /// The player's username or hash
+        player: String,
+    },
+    /// Show what changed between a saved version of an account and another version (or the account as it is now)
+    HistoryDiff {
+        /// The realm name
+        realm: String,
+        /// The player's username or hash
+        player: String,
+        /// The version to diff from
+        version: i32,
+        /// The version to diff to, defaults to the current account
+        #[arg(long)]
+        against: Option<i32>,
+    },
+    /// Put a saved version of an account back in place
+    HistoryRestore {
+        /// The realm name
+        realm: String,
+        /// The player's username or hash
+        player: String,
+        /// The version to restore
+        version: i32,
+        /// Restore even though a profile server is running, which may still serve (and save) its cached copy
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the saves that tripped an anomaly rule
+    Anomalies {
+        /// Only list anomalies in this realm
+        #[arg(long)]
+        realm: Option<String>,
+        /// Include anomalies that have already been released or dismissed
+        #[arg(long)]
+        all: bool,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Write a quarantined save into its account
+    ReleaseAnomaly {
+        /// The anomaly id
+        id: i32,
+        /// Release even though a profile server is running, which may still serve (and save) its cached copy
+        #[arg(long)]
+        force: bool,
+    },
+    /// Mark an anomaly as reviewed without touching the account
+    DismissAnomaly {
+        /// The anomaly id
+        id: i32,
+    },
+    /// List the bans in force
+    Bans {
+        /// Include bans that have been lifted or have expired
+        #[arg(long)]
+        all: bool,
+        #[command(flatten)]
+        page: Page,
+    },
+    /// Ban a sid, a player or an ip address from getting and setting profiles
+    Ban {
+        #[command(flatten)]
+        target: BanTargetArgs,
+        /// Only ban in this realm
+        #[arg(long)]
+        realm: Option<String>,
+        /// Why, shown to whoever lists the bans later
+        #[arg(long)]
+        reason: String,
+        /// Who is issuing the ban
+        #[arg(long, default_value = "cli")]
+        issued_by: String,
+        /// Lift the ban automatically after this many seconds, ten years at most like the admin api
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..=315360000))]
+        expires_in_secs: Option<u64>,
+    },
+    /// Lift a ban, it is kept in the list of bans as lifted
+    Unban {
+        /// The ban id
+        id: i32,
+        /// Who is lifting the ban
+        #[arg(long, default_value = "cli")]
+        lifted_by: String,
+    },
+    /// Delete a player's papers and their accounts in every realm
+    DeletePlayer {
+        /// The player's username or hash
+        player: String,
+        /// Go ahead even though a profile server is running, which may still serve (and save) its cached copies
+        #[arg(long)]
+        force: bool,
+    },
+    /// Delete a player's account so that they start over with an init profile, keeping their papers
+    ResetPlayer {
+        /// The player's username or hash
+        player: String,
+        /// Only reset the account in this realm
+        #[arg(long)]
+        realm: Option<String>,
+        /// Go ahead even though a profile server is running, which may still serve (and save) its cached copies
+        #[arg(long)]
+        force: bool,
+    },
+    /// Create a realm with a known digest ahead of its first request
+    SeedRealm {
+        /// The realm name, as configured on the game server
+        name: String,
+        /// The 64 character hex digest the game server will present
+        #[arg(value_parser = parse_digest)]
+        digest: String,
+    },
+    /// Register a new digest for a realm, the old one is still accepted for a grace period
+    #[command(visible_alias = "set-digest")]
+    RotateDigest {
+        /// The realm name, as configured on the game server
+        name: String,
+        /// The new 64 character hex digest the game server will present
+        #[arg(value_parser = parse_digest)]
+        digest: String,
+        /// How long the old digest remains valid, defaults to ps_digest_grace_secs (0 replaces it outright)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(..=MAX_DIGEST_GRACE_SECS))]
+        grace_secs: Option<u64>,
+    },
+    /// Import the <hash>.profile and <hash>.person files from a vanilla rwr save directory into a realm
+    ///
+    /// Overwriting accounts is refused whilst a profile server is running, as it may still have them cached
+    ImportProfiles {
+        /// The realm to import into, which must already exist (see seed-realm)
+        realm: String,
+        /// The directory holding the vanilla player files
+        dir: PathBuf,
+        /// Report what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Replace accounts that already exist in the realm instead of reporting them as conflicts
+        #[arg(long)]
+        overwrite: bool,
+        /// Overwrite even though a profile server is running, which may still serve (and save) its cached copies
+        #[arg(long)]
+        force: bool,
+    },
+    /// Export every account in a realm as vanilla rwr <hash>.profile and <hash>.person files
+    ExportProfiles {
+        /// The realm to export
+        realm: String,
+        /// The directory to write the player files to, created if needed
+        dir: PathBuf,
+        /// Overwrite player files that already exist in the directory
+        #[arg(long)]
+        force: bool,
+    },
+    /// Import the realms, players and accounts of a värväytyä-py database
+    ///
+    /// Overwriting accounts is refused whilst a profile server is running, as it may still have them cached
+    ImportVarvaytya {
+        /// The värväytyä-py db url, e.g. sqlite://varvaytya.db?mode=ro
+        source_url: String,
+        /// Report what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Replace accounts that already exist instead of reporting them as conflicts
+        #[arg(long)]
+        overwrite: bool,
+        /// Overwrite even though a profile server is running, which may still serve (and save) its cached copies
+        #[arg(long)]
+        force: bool,
+    },
+    /// Apply or roll back db migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Reclaim unused space in the db
+    Vacuum,
+    /// Rewrite account json left by older versions of marshalrwr in the current schema
+    UpgradeJson {
+        /// Report how many accounts would be upgraded without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move the items of every account over to json columns or account_item rows, before changing ps_item_storage
+    MoveItems {
+        /// Where the items should end up
+        #[arg(value_enum)]
+        storage: ItemStorage,
+        /// Report how many accounts would be moved without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Go ahead even though a profile server is running, which would keep on using the other storage
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    /// Apply pending migrations
+    Up {
+        /// Apply at most this many migrations
+        #[arg(long)]
+        steps: Option<u32>,
+    },
+    /// Roll back applied migrations
+    Down {
+        /// Roll back this many migrations
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// Show which migrations have been applied
+    Status,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(group(ArgGroup::new("target").required(true).args(["sid", "player", "ip"])))]
+pub struct BanTargetArgs {
+    /// Ban a steam id
+    #[arg(long)]
+    pub sid: Option<i64>,
+    /// Ban a player by username or hash, the player doesn't need to have enlisted yet
+    #[arg(long)]
+    pub player: Option<String>,
+    /// Ban an ip address or cidr block
+    #[arg(long)]
+    pub ip: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct Page {
+    /// The maximum number of rows to list
+    #[arg(long, default_value_t = 50)]
+    pub limit: u64,
+    /// The number of rows to skip
+    #[arg(long, default_value_t = 0)]
+    pub offset: u64,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum AccountFormat {
+    Xml,
+    Json,
+}
+
+pub async fn run(
+    command: Command,
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Realms => realms::list_realms(db).await,
+        Command::Players { realm, page } => players::list_players(db, realm.as_deref(), &page).await,
+        Command::Accounts { realm, page } => accounts::list_accounts(db, &realm, &page).await,
+        Command::Account { realm, player, format } => {
+            accounts::show_account(db, &realm, &player, format).await
+        }
+        Command::History { realm, player } => history::list_history(db, &realm, &player).await,
+        Command::HistoryDiff { realm, player, version, against } => {
+            history::diff_history(db, &realm, &player, version, against).await
+        }
+        Command::HistoryRestore { realm, player, version, force } => {
+            history::restore_history(app_config, db, &realm, &player, version, force).await
+        }
+        Command::Anomalies { realm, all, page } => {
+            anomalies::list(db, realm.as_deref(), all, &page).await
+        }
+        Command::ReleaseAnomaly { id, force } => anomalies::release(app_config, db, id, force).await,
+        Command::DismissAnomaly { id } => anomalies::dismiss(db, id).await,
+        Command::Bans { all, page } => bans::list(db, all, &page).await,
+        Command::Ban { target, realm, reason, issued_by, expires_in_secs } => {
+            bans::ban(db, &target, realm.as_deref(), &reason, &issued_by, expires_in_secs).await
+        }
+        Command::Unban { id, lifted_by } => bans::unban(db, id, &lifted_by).await,
+        Command::DeletePlayer { player, force } => players::delete_player(app_config, db, &player, force).await,
+        Command::ResetPlayer { player, realm, force } => {
+            players::reset_player(app_config, db, &player, realm.as_deref(), force).await
+        }
+        Command::SeedRealm { name, digest } => realms::seed_realm(db, &name, &digest).await,
+        Command::RotateDigest { name, digest, grace_secs } => {
+            let grace_secs = grace_secs.unwrap_or(app_config.ps_digest_grace_secs);
+            realms::rotate_digest(app_config, db, &name, &digest, grace_secs).await
+        }
+        Command::ImportProfiles { realm, dir, dry_run, overwrite, force } => {
+            profiles::import_profiles(app_config, db, &realm, &dir, dry_run, overwrite, force).await
+        }
+        Command::ExportProfiles { realm, dir, force } => {
+            profiles::export_profiles(db, &realm, &dir, force).await
+        }
+        Command::ImportVarvaytya { source_url, dry_run, overwrite, force } => {
+            varvaytya::import_varvaytya(app_config, db, &source_url, dry_run, overwrite, force).await
+        }
+        Command::Migrate { action } => db::migrate(db, action).await,
+        Command::Vacuum => db::vacuum(db).await,
+        Command::UpgradeJson { dry_run } => db::upgrade_json(db, dry_run).await,
+        Command::MoveItems { storage, dry_run, force } => {
+            db::move_items(app_config, db, storage, dry_run, force).await
+        }
+    }
+}
+
+// a running profile server holds on to the players and accounts it has cached, so it would go on serving what the cli
+// changed underneath it and could save its stale copy straight back (or fail to, for a deleted player), so changes
+// to players and accounts are refused whilst one is listening unless forced, `admin_api` is the admin api request
+// that makes the same change through the server instead, evicting its cached copies
+pub async fn check_no_running_server(
+    app_config: &AppConfiguration,
+    force: bool,
+    admin_api: Option<&str>,
+) -> anyhow::Result<()> {
+    let Some(server_addr) = find_running_server(app_config).await else {
+        return Ok(());
+    };
+    if force {
+        println!("warning: a profile server is listening on {server_addr}, it may serve and save its cached copies");
+        return Ok(());
+    }
+    let instead = match admin_api {
+        Some(admin_api) => format!("use the admin api ({admin_api}), stop the server"),
+        None => String::from("stop the server"),
+    };
+    anyhow::bail!(
+        "a profile server is listening on {server_addr} and may undo this change with its cached copies, \
+        {instead} or pass --force"
+    )
+}
+
+async fn find_running_server(app_config: &AppConfiguration) -> Option<SocketAddr> {
+    for listen_addr in app_config.listen_addrs.iter() {
+        // a server listening on every address can be reached on the loopback address
+        let ip = match listen_addr {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ip => *ip,
+        };
+        let addr = SocketAddr::new(ip, app_config.listen_port);
+        if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_secs(1), TcpStream::connect(addr)).await {
+            return Some(addr);
+        }
+    }
+    None
+}
+
+fn parse_digest(digest: &str) -> Result<String, String> {
+    if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(String::from("digest must be 64 hexadecimal characters"));
+    }
+    Ok(String::from(digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn changes_are_refused_whilst_a_server_is_listening() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let app_config = AppConfiguration {
+            listen_addrs: vec!["0.0.0.0".parse().unwrap()],
+            listen_port: listener.local_addr().unwrap().port(),
+            ..Default::default()
+        };
+        let err = check_no_running_server(&app_config, false, Some("DELETE /admin/players/1")).await.unwrap_err();
+        assert!(err.to_string().contains("DELETE /admin/players/1"));
+        check_no_running_server(&app_config, true, None).await.unwrap();
+
+        drop(listener);
+        check_no_running_server(&app_config, false, None).await.unwrap();
+    }
+
+    #[test]
+    fn ban_expiry_is_bounded() {
+        let ban = |secs: &str| {
+            Cli::try_parse_from(["marshalrwr", "ban", "--sid", "1", "--reason", "spam", "--expires-in-secs", secs])
+        };
+        assert!(ban("315360000").is_ok());
+        assert!(ban("315360001").is_err());
+        assert!(ban(&u64::MAX.to_string()).is_err());
+        assert!(ban("0").is_err());
+    }
+
+    #[test]
+    fn digest_grace_is_bounded() {
+        let rotate = |secs: &str| {
+            Cli::try_parse_from(["marshalrwr", "rotate-digest", "CLAN", &"a".repeat(64), "--grace-secs", secs])
+        };
+        assert!(rotate("0").is_ok());
+        assert!(rotate(&MAX_DIGEST_GRACE_SECS.to_string()).is_ok());
+        assert!(rotate(&(MAX_DIGEST_GRACE_SECS + 1).to_string()).is_err());
+        assert!(rotate(&u64::MAX.to_string()).is_err());
+        let app_config = AppConfiguration { ps_digest_grace_secs: u64::MAX, ..Default::default() };
+        assert!(app_config.validate().is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait,
+};
+
+use super::super::config::AppConfiguration;
+use super::super::profile_server::util::{
+    delete_accounts_from_db, delete_player_from_db, get_player_from_db, get_realm_from_db,
+};
+use super::{check_no_running_server, Page};
+use entity::{Account, AccountColumn, Player, PlayerColumn, PlayerModel};
+
+pub async fn find_player(db: &DatabaseConnection, player: &str) -> anyhow::Result<PlayerModel> {
+    // usernames come first, a username made up of digits shouldn't be mistaken for a hash
+    if let Some(player) = Player::find()
+        .filter(PlayerColumn::Username.eq(player))
+        .one(db)
+        .await?
+    {
+        return Ok(player);
+    }
+    if let Ok(hash) = player.parse::<i64>() {
+        if let Some(player) = get_player_from_db(db, hash).await? {
+            return Ok(player);
+        }
+    }
+    anyhow::bail!("player '{player}' not found")
+}
+
+pub async fn list_players(db: &DatabaseConnection, realm: Option<&str>, page: &Page) -> anyhow::Result<()> {
+    let mut query = Player::find().order_by_asc(PlayerColumn::Username);
+    if let Some(realm_name) = realm {
+        let Some(realm) = get_realm_from_db(db, realm_name).await? else {
+            anyhow::bail!("realm '{realm_name}' does not exist");
+        };
+        let hashes = Account::find()
+            .select_only()
+            .column(AccountColumn::Hash)
+            .filter(AccountColumn::RealmId.eq(realm.id))
+            .into_query();
+        query = query.filter(PlayerColumn::Hash.in_subquery(hashes));
+    }
+    let total = query.clone().count(db).await?;
+    let players = query.offset(page.offset).limit(page.limit).all(db).await?;
+    println!("{:>12}  {:<32} {:>12}", "hash", "username", "sid");
+    for player in players.iter() {
+        println!("{:>12}  {:<32} {:>12}", player.hash, player.username, player.sid);
+    }
+    println!("({} of {} players)", players.len(), total);
+    Ok(())
+}
+
+pub async fn delete_player(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    player: &str,
+    force: bool,
+) -> anyhow::Result<()> {
+    let player = find_player(db, player).await?;
+    let admin_api = format!("DELETE /admin/players/{}", player.hash);
+    check_no_running_server(app_config, force, Some(&admin_api)).await?;
+    let realm_ids = delete_player_from_db(db, player.hash).await?;
+    println!(
+        "deleted player '{}' [{}] and {} account(s)",
+        player.username,
+        player.hash,
+        realm_ids.len()
+    );
+    Ok(())
+}
+
+pub async fn reset_player(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    player: &str,
+    realm: Option<&str>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let player = find_player(db, player).await?;
+    let realm_id = match realm {
+        Some(realm_name) => match get_realm_from_db(db, realm_name).await? {
+            Some(realm) => Some(realm.id),
+            None => anyhow::bail!("realm '{realm_name}' does not exist"),
+        },
+        None => None,
+    };
+    let admin_api = realm.map(|realm_name| format!("DELETE /admin/realms/{realm_name}/accounts/{}", player.hash));
+    check_no_running_server(app_config, force, admin_api.as_deref()).await?;
+    let realm_ids = delete_accounts_from_db(db, player.hash, realm_id).await?;
+    println!(
+        "reset player '{}' [{}], deleted {} account(s)",
+        player.username,
+        player.hash,
+        realm_ids.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use sea_orm::DatabaseConnection;
+
+use super::super::config::AppConfiguration;
+use super::super::profile_server::util::get_realm_from_db;
+use super::super::vanilla::export::export_profiles as export_vanilla_profiles;
+use super::super::vanilla::import::import_profiles as import_vanilla_profiles;
+use super::check_no_running_server;
+
+pub async fn import_profiles(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    realm_name: &str,
+    dir: &Path,
+    dry_run: bool,
+    overwrite: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    let Some(realm) = get_realm_from_db(db, realm_name).await? else {
+        anyhow::bail!("realm '{realm_name}' does not exist, seed it first");
+    };
+    // only the accounts being overwritten could be sitting in the server's cache, new ones never are
+    if overwrite && !dry_run {
+        check_no_running_server(app_config, force, None).await?;
+    }
+    let report = import_vanilla_profiles(db, &realm, dir, dry_run, overwrite, app_config.ps_item_storage).await?;
+    for (hash, username) in report.imported.iter() {
+        println!("imported     '{username}' [{hash}]");
+    }
+    for (hash, username) in report.overwritten.iter() {
+        println!("overwritten  '{username}' [{hash}]");
+    }
+    for conflict in report.conflicts.iter() {
+        println!("conflict     {}: {}", conflict.file, conflict.reason);
+    }
+    println!(
+        "{}{} imported, {} overwritten, {} conflict(s) in realm '{}'",
+        if dry_run { "dry run: " } else { "" },
+        report.imported.len(),
+        report.overwritten.len(),
+        report.conflicts.len(),
+        realm.name
+    );
+    Ok(())
+}
+
+pub async fn export_profiles(
+    db: &DatabaseConnection,
+    realm_name: &str,
+    dir: &Path,
+    force: bool,
+) -> anyhow::Result<()> {
+    let Some(realm) = get_realm_from_db(db, realm_name).await? else {
+        anyhow::bail!("realm '{realm_name}' does not exist");
+    };
+    let report = export_vanilla_profiles(db, &realm, dir, force).await?;
+    for (hash, username) in report.exported.iter() {
+        println!("exported  '{username}' [{hash}]");
+    }
+    for (hash, reason) in report.skipped.iter() {
+        println!("skipped   {hash}: {reason}");
+    }
+    println!(
+        "{} exported, {} skipped from realm '{}' to '{}'",
+        report.exported.len(),
+        report.skipped.len(),
+        realm.name,
+        dir.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use chrono::Duration;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+
+use super::super::config::AppConfiguration;
+use super::super::profile_server::util::{create_realm, get_realm_from_db, rotate_realm_digest};
+use entity::{Account, AccountColumn, Realm, RealmColumn};
+
+pub async fn list_realms(db: &DatabaseConnection) -> anyhow::Result<()> {
+    let realms = Realm::find().order_by_asc(RealmColumn::Id).all(db).await?;
+    println!("{:>6}  {:<24} {:>8}  {:<18} {:<16} created", "id", "name", "accounts", "digest", "created by");
+    for realm in realms {
+        let accounts = Account::find()
+            .filter(AccountColumn::RealmId.eq(realm.id))
+            .count(db)
+            .await?;
+        // the digest is a credential, only show enough of it to tell realms apart
+        let digest = match realm.previous_digest {
+            Some(_) => format!("{}.. (rotating)", &realm.digest[..8]),
+            None => format!("{}..", &realm.digest[..8]),
+        };
+        println!(
+            "{:>6}  {:<24} {:>8}  {:<18} {:<16} {}",
+            realm.id,
+            realm.name,
+            accounts,
+            digest,
+            realm.created_by.unwrap_or_default(),
+            realm.created_at.map(|created_at| created_at.to_rfc3339()).unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+pub async fn seed_realm(db: &DatabaseConnection, name: &str, digest: &str) -> anyhow::Result<()> {
+    if let Some(realm) = get_realm_from_db(db, name).await? {
+        anyhow::bail!("realm '{}' [{}] already exists", realm.name, realm.id);
+    }
+    let realm = create_realm(db, name, digest, "cli").await?;
+    println!("seeded realm '{}' [{}]", realm.name, realm.id);
+    Ok(())
+}
+
+pub async fn rotate_digest(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    name: &str,
+    digest: &str,
+    grace_secs: u64,
+) -> anyhow::Result<()> {
+    let Some(realm) = get_realm_from_db(db, name).await? else {
+        anyhow::bail!("realm '{name}' does not exist");
+    };
+    if realm.digest.eq_ignore_ascii_case(digest) {
+        anyhow::bail!("realm '{}' [{}] already has this digest", realm.name, realm.id);
+    }
+    if app_config.realms.get(name).is_some_and(|realm| realm.digest.is_some()) {
+        println!("warning: realms.{name}.digest is pinned in the config and takes precedence over the stored digest");
+    }
+    let grace = Duration::seconds(i64::try_from(grace_secs)?);
+    let realm = rotate_realm_digest(db, realm, digest, grace, "cli").await?;
+    match realm.previous_digest_expires_at {
+        Some(expires_at) => println!(
+            "rotated the digest of realm '{}' [{}], the previous digest is accepted until {}",
+            realm.name, realm.id, expires_at
+        ),
+        None => println!(
+            "rotated the digest of realm '{}' [{}], the previous digest was retired immediately",
+            realm.name, realm.id
+        ),
+    }
+    Ok(())
+}
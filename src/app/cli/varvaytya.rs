@@ -0,0 +1,52 @@
+use sea_orm::{Database, DatabaseConnection};
+
+use super::super::config::AppConfiguration;
+use super::super::varvaytya::{import_database, TableSummary};
+use super::check_no_running_server;
+
+pub async fn import_varvaytya(
+    app_config: &AppConfiguration,
+    db: &DatabaseConnection,
+    source_url: &str,
+    dry_run: bool,
+    overwrite: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    // only the accounts being overwritten could be sitting in the server's cache, new ones never are
+    if overwrite && !dry_run {
+        check_no_running_server(app_config, force, None).await?;
+    }
+    let source = Database::connect(source_url).await?;
+    let summary = import_database(&source, db, dry_run, overwrite, app_config.ps_item_storage).await?;
+    let tables: [(&str, &TableSummary); 3] = [
+        ("realm", &summary.realms),
+        ("player", &summary.players),
+        ("account", &summary.accounts),
+    ];
+    for (_, table) in tables.iter() {
+        for conflict in table.conflicts.iter() {
+            println!("conflict  {conflict}");
+        }
+    }
+    println!(
+        "{:<8} {:>8} {:>9} {:>8} {:>12} {:>8} {:>10}  reconciled",
+        "table", "source", "inserted", "matched", "overwritten", "skipped", "conflicts"
+    );
+    for (name, table) in tables.iter() {
+        println!(
+            "{:<8} {:>8} {:>9} {:>8} {:>12} {:>8} {:>10}  {}",
+            name,
+            table.source,
+            table.inserted,
+            table.matched,
+            table.overwritten,
+            table.skipped,
+            table.conflicts.len(),
+            if table.reconciled() { "yes" } else { "NO" }
+        );
+    }
+    if dry_run {
+        println!("dry run, nothing was written");
+    }
+    Ok(())
+}
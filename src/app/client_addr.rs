@@ -0,0 +1,192 @@
+use std::net::{IpAddr, SocketAddr};
+
+use async_trait::async_trait;
+use axum::extract::{connect_info::Connected, ConnectInfo, FromRequestParts};
+use axum::http::{request::Parts, HeaderMap};
+use hyper::server::conn::AddrStream;
+
+use super::config::ForwardedHeader;
+use super::ip_rules::find_matching_rule;
+use super::listener::ProxiedStream;
+use super::profile_server::errors::ProfileServerError;
+use super::state::AppState;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PeerInfo {
+    pub remote: SocketAddr,
+    // the source address supplied by a trusted proxy in a PROXY protocol header, if any
+    pub proxied: Option<SocketAddr>,
+}
+
+impl Connected<&AddrStream> for PeerInfo {
+    fn connect_info(target: &AddrStream) -> Self {
+        Self {
+            remote: target.remote_addr(),
+            proxied: None,
+        }
+    }
+}
+
+impl Connected<&ProxiedStream> for PeerInfo {
+    fn connect_info(target: &ProxiedStream) -> Self {
+        Self {
+            remote: target.remote,
+            proxied: target.proxied,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClientAddr(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientAddr {
+    type Rejection = ProfileServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let client_addr = resolve_client_addr(parts, state)?;
+        tracing::Span::current().record("client_ip", tracing::field::display(client_addr.0));
+        Ok(client_addr)
+    }
+}
+
+fn resolve_client_addr(parts: &Parts, state: &AppState) -> Result<ClientAddr, ProfileServerError> {
+    let ConnectInfo(peer) = parts
+        .extensions
+        .get::<ConnectInfo<PeerInfo>>()
+        .copied()
+        .ok_or_else(|| ProfileServerError::ClientAddressUnknown(String::from("no connection info")))?;
+    // the PROXY protocol source is only ever accepted from trusted proxies by the listener
+    let peer_ip = peer.proxied.unwrap_or(peer.remote).ip().to_canonical();
+    if !is_trusted_proxy(state, peer_ip) {
+        return Ok(ClientAddr(peer_ip));
+    }
+
+    let forwarded_chain = match forwarded_for_chain(&parts.headers, state.config().forwarded_header) {
+        Some(Ok(chain)) => chain,
+        Some(Err(node)) => {
+            return Err(ProfileServerError::ClientAddressUnknown(format!(
+                "unparseable forwarded node '{node}' from trusted proxy '{peer_ip}'"
+            )))
+        }
+        // a trusted proxy talking to us on its own behalf (or a PROXY protocol only setup)
+        None => return Ok(ClientAddr(peer_ip)),
+    };
+    // walk the chain from the nearest hop outwards, the first untrusted hop is the client
+    let mut client_ip = peer_ip;
+    for hop in forwarded_chain.iter().rev() {
+        client_ip = hop.to_canonical();
+        if !is_trusted_proxy(state, client_ip) {
+            break;
+        }
+    }
+    tracing::debug!("client address '{client_ip}' forwarded by trusted proxy '{peer_ip}'");
+    Ok(ClientAddr(client_ip))
+}
+
+pub fn is_trusted_proxy(state: &AppState, ip: IpAddr) -> bool {
+    find_matching_rule(&state.config().trusted_proxies, ip, &state.resolver).is_some()
+}
+
+fn forwarded_for_chain(headers: &HeaderMap, header: ForwardedHeader) -> Option<Result<Vec<IpAddr>, String>> {
+    // only ever read the configured header, the proxy appends to that one and passes the other through untouched
+    let header_name = match header {
+        ForwardedHeader::XForwardedFor => "x-forwarded-for",
+        ForwardedHeader::Forwarded => "forwarded",
+    };
+    let values: Vec<&str> = headers
+        .get_all(header_name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    let elements = values.into_iter().flat_map(|value| value.split(','));
+    match header {
+        ForwardedHeader::XForwardedFor => Some(parse_nodes(elements)),
+        ForwardedHeader::Forwarded => {
+            let nodes = elements.filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    key.eq_ignore_ascii_case("for").then(|| value.trim_matches('"'))
+                })
+            });
+            Some(parse_nodes(nodes))
+        }
+    }
+}
+
+fn parse_nodes<'a>(nodes: impl Iterator<Item = &'a str>) -> Result<Vec<IpAddr>, String> {
+    nodes
+        .map(str::trim)
+        .map(|node| parse_node(node).ok_or_else(|| String::from(node)))
+        .collect()
+}
+
+fn parse_node(node: &str) -> Option<IpAddr> {
+    // nodes come as "ip", "ipv4:port", "[ipv6]" or "[ipv6]:port"
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .and_then(|ip| ip.parse::<IpAddr>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+    use crate::app::config::AppConfiguration;
+    use crate::app::testing::sqlite_db;
+
+    async fn resolve(forwarded_header: ForwardedHeader, headers: &[(&str, &str)]) -> IpAddr {
+        let app_config = AppConfiguration {
+            trusted_proxies: vec!["10.0.0.1".parse().unwrap()],
+            forwarded_header,
+            ..Default::default()
+        };
+        let state = AppState::new(app_config, sqlite_db().await, None);
+        let peer = PeerInfo { remote: "10.0.0.1:50000".parse().unwrap(), proxied: None };
+        let mut request = Request::builder().extension(ConnectInfo(peer));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let (parts, _) = request.body(()).unwrap().into_parts();
+        resolve_client_addr(&parts, &state).unwrap().0
+    }
+
+    #[tokio::test]
+    async fn spoofed_forwarded_header_is_ignored() {
+        // the client sent its own Forwarded header, the proxy passed it on and appended the real address to XFF
+        let headers = [("forwarded", "for=127.0.0.1"), ("x-forwarded-for", "203.0.113.7")];
+        let client_ip = resolve(ForwardedHeader::XForwardedFor, &headers).await;
+        assert_eq!(client_ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn spoofed_x_forwarded_for_header_is_ignored() {
+        let headers = [("x-forwarded-for", "127.0.0.1"), ("forwarded", "for=\"[2001:db8::7]:4321\"")];
+        let client_ip = resolve(ForwardedHeader::Forwarded, &headers).await;
+        assert_eq!(client_ip, "2001:db8::7".parse::<IpAddr>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn only_the_configured_header_is_read() {
+        // without the configured header the proxy is taken to be talking on its own behalf
+        let client_ip = resolve(ForwardedHeader::XForwardedFor, &[("forwarded", "for=127.0.0.1")]).await;
+        assert_eq!(client_ip, "10.0.0.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn trusted_hops_are_skipped() {
+        let headers = [("x-forwarded-for", "127.0.0.1, 203.0.113.7, 10.0.0.1")];
+        let client_ip = resolve(ForwardedHeader::XForwardedFor, &headers).await;
+        assert_eq!(client_ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+    }
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashSet, net::IpAddr, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    path::PathBuf,
+    str::FromStr,
+};
 
 use figment::{
     providers::{Env, Format, Serialized, Toml},
@@ -6,32 +11,333 @@ use figment::{
 };
 use serde::{Deserialize, Serialize};
 
+use super::ip_rules::IpRule;
+use super::DB_DEFAULT_URL;
+
+pub const CONFIG_FILE: &str = "marshalrwr.toml";
+
+// ten years, past that a previous digest might as well never be retired
+pub const MAX_DIGEST_GRACE_SECS: u64 = 315360000;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppConfiguration {
+    pub listen_addrs: Vec<IpAddr>,
+    pub listen_port: u16,
+    pub db_url: String,
+    pub trusted_proxies: Vec<IpRule>,
+    pub forwarded_header: ForwardedHeader,
+    pub proxy_protocol: bool,
+    pub shutdown_drain_secs: u64,
+    pub config_watch_secs: u64,
+    pub log_format: LogFormat,
+    pub log_file: Option<PathBuf>,
+    pub log_file_format: LogFormat,
+    pub log_file_rotation: LogRotation,
+    pub log_file_keep: usize,
+    pub admin_token: Option<String>,
+    pub metrics_allowed_ips: Vec<IpRule>,
     pub ps_realms: HashSet<String>,
-    pub ps_allowed_ips: HashSet<IpAddr>,
+    pub realms: HashMap<String, RealmConfiguration>,
+    pub ps_strict_realms: bool,
+    pub ps_digest_grace_secs: u64,
+    pub ps_account_history_limit: u64,
+    pub ps_anomaly_rules: AnomalyRules,
+    pub ps_item_catalog: Option<PathBuf>,
+    pub ps_item_policy: ItemPolicy,
+    pub ps_item_storage: ItemStorage,
+    pub ps_allowed_ips: Vec<IpRule>,
+    pub ps_allowed_hosts_refresh_secs: u64,
     pub ps_allowed_sids: HashSet<i64>,
     pub ps_blocked_sids: HashSet<i64>,
+    pub ps_ip_rate_limit: Option<RateLimit>,
+    pub ps_realm_rate_limit: Option<RateLimit>,
+    pub ps_enlist_rate_limit: Option<RateLimit>,
+}
+
+// the rules for a single realm, any list left unset falls back to the global ps_* list
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RealmConfiguration {
+    #[serde(skip)]
+    pub name: String,
+    pub allowed_ips: Option<Vec<IpRule>>,
+    pub allowed_sids: Option<HashSet<i64>>,
+    pub blocked_sids: Option<HashSet<i64>>,
+    pub digest: Option<String>,
+    pub strict: Option<bool>,
+    pub read_only: bool,
+    pub max_players: Option<u64>,
+    pub anomaly_rules: Option<AnomalyRules>,
+    pub item_policy: Option<ItemPolicy>,
+    pub rate_limit: Option<RateLimit>,
+    pub enlist_rate_limit: Option<RateLimit>,
+}
+
+// how log lines are written: for people (ansi and emoji) or for log shippers (a json object per line)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Console,
+    Json,
+}
+
+// how often the log file moves on to a new file, named after the period it covers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+// what to do with items in a save that aren't in the item catalog (or are filed under the wrong class)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemPolicy {
+    Off,
+    #[default]
+    Log,
+    Drop,
+    Reject,
+}
+
+impl ItemPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemPolicy::Off => "off",
+            ItemPolicy::Log => "log",
+            ItemPolicy::Drop => "drop",
+            ItemPolicy::Reject => "reject",
+        }
+    }
+}
+
+// what to do with an incoming save that trips an anomaly rule, in increasing order of severity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnomalyAction {
+    #[default]
+    Off,
+    Warn,
+    Quarantine,
+    Reject,
+}
+
+impl AnomalyAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyAction::Off => "off",
+            AnomalyAction::Warn => "warn",
+            AnomalyAction::Quarantine => "quarantine",
+            AnomalyAction::Reject => "reject",
+        }
+    }
+}
+
+// the one header a trusted proxy passes the client address in, the other is whatever the client sent and is ignored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardedHeader {
+    #[default]
+    XForwardedFor,
+    Forwarded,
+}
+
+// where account loadouts, backpacks and stashes are written: json columns on the account or account_item rows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemStorage {
+    #[default]
+    Json,
+    Table,
+}
+
+// a token bucket: bursts of up to `requests`, refilled at `requests` per `per_secs` seconds
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per_secs: u64,
+}
+
+impl RateLimit {
+    fn validate(&self, key: &str) -> Result<(), String> {
+        if self.requests == 0 || self.per_secs == 0 {
+            return Err(format!("{key}.requests and {key}.per_secs must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+// the rules an incoming save is checked against, relative to the account already stored
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyRules {
+    // lifetime counters (kills, deaths, time played, shots fired, ...) going backwards
+    pub stat_regression: AnomalyAction,
+    // authority climbing by more than max_authority_gain in a single save
+    pub authority_jump: AnomalyAction,
+    pub max_authority_gain: f64,
+    // kills climbing faster than max_kills_per_hour of the time played since the last save
+    pub kill_rate: AnomalyAction,
+    pub max_kills_per_hour: f64,
+}
+
+impl Default for AnomalyRules {
+    fn default() -> Self {
+        AnomalyRules {
+            stat_regression: AnomalyAction::Warn,
+            authority_jump: AnomalyAction::Warn,
+            max_authority_gain: 10.0,
+            kill_rate: AnomalyAction::Off,
+            max_kills_per_hour: 600.0,
+        }
+    }
+}
+
+impl AnomalyRules {
+    fn validate(&self, key: &str) -> Result<(), String> {
+        if self.max_authority_gain.is_nan() || self.max_authority_gain <= 0.0 {
+            return Err(format!("{key}.max_authority_gain must be greater than 0"));
+        }
+        if self.max_kills_per_hour.is_nan() || self.max_kills_per_hour <= 0.0 {
+            return Err(format!("{key}.max_kills_per_hour must be greater than 0"));
+        }
+        Ok(())
+    }
 }
 
 impl Default for AppConfiguration {
     fn default() -> Self {
         AppConfiguration {
+            listen_addrs: vec![IpAddr::from_str("127.0.0.1").unwrap()],
+            listen_port: 4321,
+            db_url: format!("{DB_DEFAULT_URL}?mode=rwc"),
+            trusted_proxies: Vec::new(),
+            forwarded_header: ForwardedHeader::default(),
+            proxy_protocol: false,
+            shutdown_drain_secs: 0,
+            config_watch_secs: 5,
+            log_format: LogFormat::Console,
+            log_file: None,
+            log_file_format: LogFormat::Json,
+            log_file_rotation: LogRotation::default(),
+            log_file_keep: 7,
+            admin_token: None,
+            metrics_allowed_ips: vec![
+                IpRule::Addr(IpAddr::from_str("127.0.0.1").unwrap()),
+                IpRule::Addr(IpAddr::from_str("::1").unwrap()),
+            ],
             ps_realms: HashSet::new(),
-            ps_allowed_ips: HashSet::from([IpAddr::from_str("127.0.0.1").unwrap()]),
+            realms: HashMap::new(),
+            ps_strict_realms: false,
+            ps_digest_grace_secs: 86400,
+            ps_account_history_limit: 10,
+            ps_anomaly_rules: AnomalyRules::default(),
+            ps_item_catalog: None,
+            ps_item_policy: ItemPolicy::default(),
+            ps_item_storage: ItemStorage::default(),
+            ps_allowed_ips: vec![IpRule::Addr(IpAddr::from_str("127.0.0.1").unwrap())],
+            ps_allowed_hosts_refresh_secs: 300,
             ps_allowed_sids: HashSet::new(),
             ps_blocked_sids: HashSet::new(),
+            ps_ip_rate_limit: None,
+            ps_realm_rate_limit: None,
+            ps_enlist_rate_limit: None,
         }
     }
 }
 
 impl AppConfiguration {
-    pub fn build() -> Result<Self, figment::Error> {
-        let app_config: AppConfiguration =
-            Figment::from(Serialized::defaults(AppConfiguration::default()))
-                .merge(Toml::file("marshalrwr.toml"))
-                .merge(Env::prefixed("MRWR_"))
-                .extract()?;
+    pub fn build() -> Result<Self, Box<figment::Error>> {
+        let app_config = AppConfiguration::load()?;
+        app_config.validate().map_err(figment::Error::from)?;
         Ok(app_config)
     }
+
+    // reads the configuration without validating it, so that a reload can report what a rejected config changed
+    pub fn load() -> Result<Self, Box<figment::Error>> {
+        Ok(Figment::from(Serialized::defaults(AppConfiguration::default()))
+            .merge(Toml::file(CONFIG_FILE))
+            .merge(Env::prefixed("MRWR_"))
+            .extract()?)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.admin_token.as_ref().is_some_and(|token| token.len() < 32) {
+            return Err(String::from("admin_token must be at least 32 characters"));
+        }
+        if self.ps_digest_grace_secs > MAX_DIGEST_GRACE_SECS {
+            return Err(format!("ps_digest_grace_secs must be at most {MAX_DIGEST_GRACE_SECS} (ten years)"));
+        }
+        self.ps_anomaly_rules.validate("ps_anomaly_rules")?;
+        for (key, rate_limit) in [
+            ("ps_ip_rate_limit", &self.ps_ip_rate_limit),
+            ("ps_realm_rate_limit", &self.ps_realm_rate_limit),
+            ("ps_enlist_rate_limit", &self.ps_enlist_rate_limit),
+        ] {
+            if let Some(rate_limit) = rate_limit {
+                rate_limit.validate(key)?;
+            }
+        }
+        for (name, realm) in self.realms.iter() {
+            if let Some(anomaly_rules) = &realm.anomaly_rules {
+                anomaly_rules.validate(&format!("realms.{name}.anomaly_rules"))?;
+            }
+            if let Some(rate_limit) = &realm.rate_limit {
+                rate_limit.validate(&format!("realms.{name}.rate_limit"))?;
+            }
+            if let Some(rate_limit) = &realm.enlist_rate_limit {
+                rate_limit.validate(&format!("realms.{name}.enlist_rate_limit"))?;
+            }
+            if let Some(digest) = &realm.digest {
+                if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("realms.{name}.digest must be 64 hexadecimal characters"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn realm(&self, name: &str) -> Option<RealmConfiguration> {
+        // realms listed in ps_realms without a [realms.<NAME>] block simply use the global rules
+        let mut realm_config = match self.realms.get(name) {
+            Some(realm_config) => realm_config.clone(),
+            None if self.ps_realms.contains(name) => RealmConfiguration::default(),
+            None => return None,
+        };
+        realm_config.name = String::from(name);
+        Some(realm_config)
+    }
+
+    pub fn anomaly_rules<'a>(&'a self, realm_config: &'a RealmConfiguration) -> &'a AnomalyRules {
+        // a realm's rules replace the global rules wholesale rather than merging with them
+        realm_config.anomaly_rules.as_ref().unwrap_or(&self.ps_anomaly_rules)
+    }
+
+    pub fn item_policy(&self, realm_config: &RealmConfiguration) -> ItemPolicy {
+        realm_config.item_policy.unwrap_or(self.ps_item_policy)
+    }
+
+    pub fn realm_rate_limit(&self, realm_config: &RealmConfiguration) -> Option<RateLimit> {
+        realm_config.rate_limit.or(self.ps_realm_rate_limit)
+    }
+
+    pub fn enlist_rate_limit(&self, realm_config: &RealmConfiguration) -> Option<RateLimit> {
+        realm_config.enlist_rate_limit.or(self.ps_enlist_rate_limit)
+    }
+
+    pub fn hostnames(&self) -> HashSet<String> {
+        self.ps_allowed_ips
+            .iter()
+            .chain(self.trusted_proxies.iter())
+            .chain(self.metrics_allowed_ips.iter())
+            .chain(self.realms.values().flat_map(|realm| realm.allowed_ips.iter().flatten()))
+            .filter_map(|rule| match rule {
+                IpRule::Host(host) => Some(host.to_owned()),
+                _ => None,
+            })
+            .collect()
+    }
 }
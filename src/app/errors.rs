@@ -1,10 +1,14 @@
-use axum::extract::rejection::QueryRejection;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 use sea_orm::error::DbErr;
+use serde_json::json;
 use thiserror::Error;
 use validator::ValidationErrors;
 
+use super::profile_server::errors::ProfileServerError;
+
 #[derive(Debug, Error)]
 pub enum ServerError {
     #[error(transparent)]
@@ -12,19 +16,44 @@ pub enum ServerError {
     #[error(transparent)]
     AxumQueryRejection(#[from] QueryRejection),
     #[error(transparent)]
+    AxumJsonRejection(#[from] JsonRejection),
+    #[error(transparent)]
     SeaOrmDbError(#[from] DbErr),
+    #[error(transparent)]
+    ProfileServerError(#[from] ProfileServerError),
+    #[error("admin token missing or incorrect")]
+    AdminTokenIncorrect,
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
 }
 
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
-        match self {
+        let (status, message) = match self {
             ServerError::ValidationError(_) => {
                 let message = format!("Input validation error: [{self}]").replace('\n', ", ");
                 (StatusCode::BAD_REQUEST, message)
             }
             ServerError::AxumQueryRejection(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            ServerError::AxumJsonRejection(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             ServerError::SeaOrmDbError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            // losing a race to resolve an anomaly is no different from finding it already resolved
+            ServerError::ProfileServerError(ProfileServerError::AnomalyAlreadyResolved(_)) => {
+                (StatusCode::CONFLICT, self.to_string())
+            }
+            ServerError::ProfileServerError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            ServerError::AdminTokenIncorrect => (StatusCode::UNAUTHORIZED, self.to_string()),
+            ServerError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            ServerError::Conflict(_) => (StatusCode::CONFLICT, self.to_string()),
+        };
+        // the caller got it wrong, that's worth a look but it isn't our failure
+        if status.is_server_error() {
+            tracing::error!("{message}");
+        } else {
+            tracing::warn!("{message}");
         }
-        .into_response()
+        (status, Json(json!({ "error": message }))).into_response()
     }
 }
@@ -0,0 +1,93 @@
+use std::sync::atomic::Ordering;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use sea_orm::{ConnectionTrait, Statement};
+
+use super::client_addr::ClientAddr;
+use super::ip_rules::find_matching_rule;
+use super::state::AppState;
+use migration::{Migrator, MigratorTrait};
+
+// the process is up and serving requests, nothing more
+pub async fn healthz_handler() -> &'static str {
+    "ok"
+}
+
+// whether we should be sent traffic: the db answers, its schema is current and we aren't shutting down,
+// anyone can ask so the reason given is kept short, the details only go to the log and the cache sizes
+// only go to those allowed to see the metrics
+pub async fn readyz_handler(
+    client_addr: Option<ClientAddr>,
+    State(state): State<AppState>,
+) -> (StatusCode, String) {
+    let (status, reason) = check_readiness(&state).await;
+    let caches = state.cache.entry_counts();
+    tracing::debug!("readiness: {reason}, caches: {caches:?}");
+    let metrics_allowed = client_addr.is_some_and(|ClientAddr(client_ip)| {
+        find_matching_rule(&state.config().metrics_allowed_ips, client_ip, &state.resolver).is_some()
+    });
+    if !metrics_allowed {
+        return (status, reason.to_owned());
+    }
+    let cache_lines: String = caches.iter().map(|(name, count)| format!("\ncache {name} {count}")).collect();
+    (status, format!("{reason}{cache_lines}"))
+}
+
+async fn check_readiness(state: &AppState) -> (StatusCode, &'static str) {
+    if state.draining.load(Ordering::Relaxed) {
+        tracing::warn!("not ready: draining");
+        return (StatusCode::SERVICE_UNAVAILABLE, "draining");
+    }
+    let backend = state.db.get_database_backend();
+    if let Err(err) = state.db.query_one(Statement::from_string(backend, String::from("SELECT 1"))).await {
+        tracing::warn!("not ready: db unavailable: {err}");
+        return (StatusCode::SERVICE_UNAVAILABLE, "db unavailable");
+    }
+    let pending_migrations: Vec<String> = match Migrator::get_migration_models(&state.db).await {
+        Ok(applied) => Migrator::migrations()
+            .iter()
+            .map(|migration| migration.name().to_owned())
+            .filter(|name| !applied.iter().any(|model| &model.version == name))
+            .collect(),
+        Err(err) => {
+            tracing::warn!("not ready: failed to list applied migrations: {err}");
+            return (StatusCode::SERVICE_UNAVAILABLE, "db unavailable");
+        }
+    };
+    if !pending_migrations.is_empty() {
+        tracing::warn!("not ready: pending migrations: {}", pending_migrations.join(", "));
+        return (StatusCode::SERVICE_UNAVAILABLE, "migrations pending");
+    }
+    (StatusCode::OK, "ok")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::*;
+    use crate::app::config::AppConfiguration;
+    use crate::app::testing::sqlite_db;
+
+    #[tokio::test]
+    async fn readiness_gives_nothing_away() {
+        // the default metrics_allowed_ips are the loopback addresses
+        let state = AppState::new(AppConfiguration::default(), sqlite_db().await, None);
+        let outsider = || Some(ClientAddr(IpAddr::from([203, 0, 113, 7])));
+        let insider = || Some(ClientAddr(IpAddr::from([127, 0, 0, 1])));
+        let ready = |reason: &str| (StatusCode::OK, reason.to_owned());
+        let not_ready = |reason: &str| (StatusCode::SERVICE_UNAVAILABLE, reason.to_owned());
+        assert_eq!(readyz_handler(None, State(state.clone())).await, ready("ok"));
+        assert_eq!(readyz_handler(outsider(), State(state.clone())).await, ready("ok"));
+        let caches = "\ncache realms 0\ncache players 0\ncache accounts 0\ncache bans 0\ncache realm_accounts 0";
+        assert_eq!(readyz_handler(insider(), State(state.clone())).await, ready(&format!("ok{caches}")));
+
+        state.draining.store(true, Ordering::Relaxed);
+        assert_eq!(readyz_handler(outsider(), State(state.clone())).await, not_ready("draining"));
+        state.draining.store(false, Ordering::Relaxed);
+        state.db.clone().close().await.unwrap();
+        assert_eq!(readyz_handler(outsider(), State(state.clone())).await, not_ready("db unavailable"));
+        assert_eq!(readyz_handler(insider(), State(state)).await, not_ready(&format!("db unavailable{caches}")));
+    }
+}
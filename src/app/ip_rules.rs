@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum IpRule {
+    Addr(IpAddr),
+    Net(IpNet),
+    Host(String),
+}
+
+impl IpRule {
+    pub fn matches(&self, ip: IpAddr, resolver: &HostResolver) -> bool {
+        match self {
+            IpRule::Addr(addr) => *addr == ip,
+            IpRule::Net(net) => net.contains(&ip),
+            IpRule::Host(host) => resolver.lookup(host).contains(&ip),
+        }
+    }
+}
+
+impl FromStr for IpRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(addr) = s.parse::<IpAddr>() {
+            return Ok(IpRule::Addr(addr));
+        }
+        if let Ok(net) = s.parse::<IpNet>() {
+            // store the network address so that e.g. "10.1.2.3/8" displays as the "10.0.0.0/8" it matches
+            return Ok(IpRule::Net(net.trunc()));
+        }
+        let is_hostname = !s.is_empty()
+            && s.len() <= 253
+            && s.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if is_hostname {
+            return Ok(IpRule::Host(s.to_ascii_lowercase()));
+        }
+        Err(format!("'{s}' is not an ip address, cidr block or hostname"))
+    }
+}
+
+impl TryFrom<String> for IpRule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpRule> for String {
+    fn from(rule: IpRule) -> Self {
+        rule.to_string()
+    }
+}
+
+impl fmt::Display for IpRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpRule::Addr(addr) => write!(f, "{addr}"),
+            IpRule::Net(net) => write!(f, "{net}"),
+            IpRule::Host(host) => write!(f, "{host}"),
+        }
+    }
+}
+
+pub fn find_matching_rule<'a>(
+    rules: &'a [IpRule],
+    ip: IpAddr,
+    resolver: &HostResolver,
+) -> Option<&'a IpRule> {
+    // ipv4 clients can show up as ipv4-mapped ipv6 addresses, compare them as plain ipv4
+    let ip = ip.to_canonical();
+    rules.iter().find(|rule| rule.matches(ip, resolver))
+}
+
+#[derive(Clone, Default)]
+pub struct HostResolver {
+    hosts: Arc<RwLock<HashSet<String>>>,
+    resolved: Arc<RwLock<HashMap<String, Vec<IpAddr>>>>,
+}
+
+impl HostResolver {
+    pub fn lookup(&self, host: &str) -> Vec<IpAddr> {
+        self.resolved
+            .read()
+            .unwrap()
+            .get(host)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn set_hosts(&self, hosts: HashSet<String>) {
+        *self.hosts.write().unwrap() = hosts;
+        self.refresh().await;
+    }
+
+    pub async fn refresh(&self) {
+        let hosts = self.hosts.read().unwrap().clone();
+        for host in hosts.iter() {
+            match tokio::net::lookup_host((host.as_str(), 0)).await {
+                Ok(addrs) => {
+                    let mut ips: Vec<IpAddr> = addrs.map(|addr| addr.ip().to_canonical()).collect();
+                    ips.sort();
+                    ips.dedup();
+                    let previous = self.resolved.write().unwrap().insert(host.to_owned(), ips.clone());
+                    if previous.as_ref() != Some(&ips) {
+                        tracing::info!("resolved host '{host}' to {ips:?}");
+                    }
+                }
+                Err(err) => {
+                    // keep whatever the host resolved to last time rather than locking the game servers out
+                    tracing::warn!("failed to resolve host '{host}', keeping previous addresses: {err}");
+                }
+            }
+        }
+        // forget hosts that are no longer referenced by any rule
+        self.resolved.write().unwrap().retain(|host, _| hosts.contains(host));
+    }
+
+    pub fn spawn_refresh(&self, interval: Duration) {
+        if interval.is_zero() {
+            return;
+        }
+        let resolver = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the first tick completes immediately and the hosts were already resolved at startup
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if resolver.hosts.read().unwrap().is_empty() {
+                    continue;
+                }
+                tracing::debug!("re-resolving allowlist hosts...");
+                resolver.refresh().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Vec<IpRule> {
+        rules.iter().map(|rule| rule.parse().unwrap()).collect()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn cidr_blocks_and_addresses_match() {
+        let resolver = HostResolver::default();
+        let rules = rules(&["10.1.2.3/8", "2001:db8::/32", "192.168.0.7"]);
+        assert_eq!(rules[0].to_string(), "10.0.0.0/8");
+        assert_eq!(find_matching_rule(&rules, ip("10.200.0.1"), &resolver), Some(&rules[0]));
+        assert_eq!(find_matching_rule(&rules, ip("11.0.0.1"), &resolver), None);
+        assert_eq!(find_matching_rule(&rules, ip("2001:db8:1::1"), &resolver), Some(&rules[1]));
+        assert_eq!(find_matching_rule(&rules, ip("2001:db9::1"), &resolver), None);
+        assert_eq!(find_matching_rule(&rules, ip("192.168.0.7"), &resolver), Some(&rules[2]));
+        assert_eq!(find_matching_rule(&rules, ip("192.168.0.8"), &resolver), None);
+        // ipv4 clients on a dual-stack socket are matched as plain ipv4
+        assert_eq!(find_matching_rule(&rules, ip("::ffff:192.168.0.7"), &resolver), Some(&rules[2]));
+        assert!("not a host!".parse::<IpRule>().is_err());
+    }
+
+    #[tokio::test]
+    async fn hostnames_match_what_they_resolved_to() {
+        let resolver = HostResolver::default();
+        let rules = rules(&["LOCALHOST"]);
+        assert_eq!(rules[0], IpRule::Host(String::from("localhost")));
+        // nothing matches until the host has been resolved
+        assert_eq!(find_matching_rule(&rules, ip("127.0.0.1"), &resolver), None);
+
+        resolver.set_hosts(HashSet::from([String::from("localhost")])).await;
+        assert_eq!(find_matching_rule(&rules, ip("127.0.0.1"), &resolver), Some(&rules[0]));
+        assert_eq!(find_matching_rule(&rules, ip("10.0.0.1"), &resolver), None);
+
+        // hosts no longer referenced are forgotten on the next refresh
+        resolver.set_hosts(HashSet::new()).await;
+        assert!(resolver.lookup("localhost").is_empty());
+        assert_eq!(find_matching_rule(&rules, ip("127.0.0.1"), &resolver), None);
+    }
+}
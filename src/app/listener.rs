@@ -0,0 +1,300 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener as StdTcpListener};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::server::accept::Accept;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use super::client_addr::is_trusted_proxy;
+use super::state::AppState;
+
+const PROXY_V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const PROXY_V1_MAX_LENGTH: usize = 107;
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn bind_listener(addr: SocketAddr) -> std::io::Result<StdTcpListener> {
+    let domain = match addr {
+        SocketAddr::V4(_) => Domain::IPV4,
+        SocketAddr::V6(_) => Domain::IPV6,
+    };
+    let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+    // an ipv6 socket would otherwise also claim the ipv4 port on dual-stack hosts,
+    // which stops us from binding e.g. both "0.0.0.0" and "::" side by side
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+pub struct ProxiedStream {
+    stream: TcpStream,
+    pub remote: SocketAddr,
+    pub proxied: Option<SocketAddr>,
+}
+
+impl AsyncRead for ProxiedStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ProxiedStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+pub struct ProxyProtocolIncoming {
+    streams: mpsc::Receiver<ProxiedStream>,
+}
+
+impl ProxyProtocolIncoming {
+    pub fn new(listener: StdTcpListener, state: AppState) -> io::Result<Self> {
+        let listener = TcpListener::from_std(listener)?;
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        tracing::warn!("failed to accept connection: {err}");
+                        continue;
+                    }
+                };
+                if tx.is_closed() {
+                    break;
+                }
+                let tx = tx.clone();
+                let state = state.clone();
+                // read the header off to the side so that a slow proxy can't stall the accept loop
+                tokio::spawn(async move {
+                    if let Some(stream) = accept_proxied(stream, remote, &state).await {
+                        let _ = tx.send(stream).await;
+                    }
+                });
+            }
+        });
+        Ok(Self { streams: rx })
+    }
+}
+
+impl Accept for ProxyProtocolIncoming {
+    type Conn = ProxiedStream;
+    type Error = io::Error;
+
+    fn poll_accept(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.streams.poll_recv(cx).map(|stream| stream.map(Ok))
+    }
+}
+
+async fn accept_proxied(mut stream: TcpStream, remote: SocketAddr, state: &AppState) -> Option<ProxiedStream> {
+    // only trusted proxies get to speak the PROXY protocol, everyone else is served as-is
+    if !is_trusted_proxy(state, remote.ip().to_canonical()) {
+        return Some(ProxiedStream { stream, remote, proxied: None });
+    }
+    match tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(&mut stream)).await {
+        Ok(Ok(proxied)) => {
+            tracing::debug!("PROXY header from trusted proxy '{remote}' gave source {proxied:?}");
+            Some(ProxiedStream { stream, remote, proxied })
+        }
+        Ok(Err(err)) => {
+            tracing::warn!("dropping connection from trusted proxy '{remote}', bad PROXY header: {err}");
+            None
+        }
+        Err(_) => {
+            tracing::warn!("dropping connection from trusted proxy '{remote}', timed out waiting for PROXY header");
+            None
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+async fn read_proxy_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    // the shortest valid header of either version is at least 12 bytes, so this never over-reads
+    let mut prefix = [0u8; 12];
+    stream.read_exact(&mut prefix).await?;
+    if prefix == PROXY_V2_SIGNATURE {
+        read_proxy_v2_header(stream).await
+    } else if prefix.starts_with(b"PROXY ") {
+        read_proxy_v1_header(stream, &prefix).await
+    } else {
+        Err(invalid("missing PROXY protocol signature"))
+    }
+}
+
+async fn read_proxy_v1_header<S: AsyncRead + Unpin>(stream: &mut S, prefix: &[u8]) -> io::Result<Option<SocketAddr>> {
+    // e.g. "PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n", read byte by byte to leave the http request alone
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= PROXY_V1_MAX_LENGTH {
+            return Err(invalid("v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2]).map_err(|_| invalid("v1 header not utf-8"))?;
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", src_ip, _dst_ip, src_port, _dst_port] => {
+            let ip: IpAddr = src_ip.parse().map_err(|_| invalid("v1 source address invalid"))?;
+            let port: u16 = src_port.parse().map_err(|_| invalid("v1 source port invalid"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("v1 header malformed")),
+    }
+}
+
+async fn read_proxy_v2_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<SocketAddr>> {
+    let version_command = stream.read_u8().await?;
+    let family_protocol = stream.read_u8().await?;
+    let length = stream.read_u16().await? as usize;
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).await?;
+    if version_command >> 4 != 2 {
+        return Err(invalid("v2 header version unsupported"));
+    }
+    // LOCAL connections (health checks from the proxy itself) carry no source address
+    if version_command & 0x0f == 0x0 {
+        return Ok(None);
+    }
+    match family_protocol >> 4 {
+        // AF_INET: src addr (4), dst addr (4), src port (2), dst port (2)
+        0x1 if body.len() >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        // AF_INET6: src addr (16), dst addr (16), src port (2), dst port (2)
+        0x2 if body.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[..16]);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port)))
+        }
+        // AF_UNSPEC or AF_UNIX, nothing useful to extract
+        0x0 | 0x3 => Ok(None),
+        _ => Err(invalid("v2 header address block malformed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::app::config::AppConfiguration;
+    use crate::app::testing::sqlite_db;
+
+    async fn parse(header: &[u8]) -> io::Result<Option<SocketAddr>> {
+        read_proxy_header(&mut &header[..]).await
+    }
+
+    fn v2_header(command: u8, family: u8, body: &[u8]) -> Vec<u8> {
+        let mut header = PROXY_V2_SIGNATURE.to_vec();
+        header.extend([0x20 | command, family]);
+        header.extend((body.len() as u16).to_be_bytes());
+        header.extend(body);
+        header
+    }
+
+    #[tokio::test]
+    async fn v1_headers_parse() {
+        let parsed = parse(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /").await.unwrap();
+        assert_eq!(parsed, Some("192.0.2.1:56324".parse().unwrap()));
+        let parsed = parse(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n").await.unwrap();
+        assert_eq!(parsed, Some("[2001:db8::1]:56324".parse().unwrap()));
+        assert_eq!(parse(b"PROXY UNKNOWN\r\n").await.unwrap(), None);
+
+        assert!(parse(b"PROXY TCP4 192.0.2.1 198.51.100.1\r\n").await.is_err());
+        assert!(parse(b"PROXY TCP4 not-an-ip 198.51.100.1 56324 443\r\n").await.is_err());
+        assert!(parse(format!("PROXY TCP4 {}\r\n", "1".repeat(PROXY_V1_MAX_LENGTH)).as_bytes()).await.is_err());
+        assert!(parse(b"GET / HTTP/1.1\r\n\r\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn v2_headers_parse() {
+        let mut body = vec![192, 0, 2, 1, 198, 51, 100, 1];
+        body.extend(56324u16.to_be_bytes());
+        body.extend(443u16.to_be_bytes());
+        let parsed = parse(&v2_header(0x1, 0x11, &body)).await.unwrap();
+        assert_eq!(parsed, Some("192.0.2.1:56324".parse().unwrap()));
+
+        let mut body = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+        body.extend("2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        body.extend(56324u16.to_be_bytes());
+        body.extend(443u16.to_be_bytes());
+        let parsed = parse(&v2_header(0x1, 0x21, &body)).await.unwrap();
+        assert_eq!(parsed, Some("[2001:db8::1]:56324".parse().unwrap()));
+
+        // LOCAL connections from the proxy itself carry no address
+        assert_eq!(parse(&v2_header(0x0, 0x00, &[])).await.unwrap(), None);
+        // an address block too short for its family
+        assert!(parse(&v2_header(0x1, 0x11, &[192, 0, 2, 1])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn truncated_headers_fail() {
+        assert!(parse(b"PROXY TCP4 192.0.2.1 198.51.100.1 5").await.is_err());
+        let header = v2_header(0x1, 0x11, &[192, 0, 2, 1, 198, 51, 100, 1, 0, 80, 1, 187]);
+        assert!(parse(&header[..header.len() - 4]).await.is_err());
+        assert!(parse(&PROXY_V2_SIGNATURE[..8]).await.is_err());
+    }
+
+    // a connection to a fresh listener, with what the peer sends already written
+    async fn connect(sent: &[u8]) -> (TcpStream, SocketAddr, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        peer.write_all(sent).await.unwrap();
+        let (stream, remote) = listener.accept().await.unwrap();
+        (stream, remote, peer)
+    }
+
+    #[tokio::test]
+    async fn only_trusted_proxies_send_headers() {
+        let header = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n";
+        let untrusted = AppState::new(AppConfiguration::default(), sqlite_db().await, None);
+        let app_config = AppConfiguration { trusted_proxies: vec!["127.0.0.1".parse().unwrap()], ..Default::default() };
+        let trusted = AppState::new(app_config, sqlite_db().await, None);
+
+        // from anyone else the header is left in the stream, where it makes for a bad http request
+        let (stream, remote, _peer) = connect(header).await;
+        let mut proxied = accept_proxied(stream, remote, &untrusted).await.unwrap();
+        assert_eq!(proxied.proxied, None);
+        let mut sent = vec![0u8; header.len()];
+        proxied.read_exact(&mut sent).await.unwrap();
+        assert_eq!(sent, header);
+
+        let (stream, remote, _peer) = connect(header).await;
+        let proxied = accept_proxied(stream, remote, &trusted).await.unwrap();
+        assert_eq!(proxied.proxied, Some("192.0.2.1:56324".parse().unwrap()));
+        let (stream, remote, _peer) = connect(b"GET / HTTP/1.1\r\n\r\n").await;
+        assert!(accept_proxied(stream, remote, &trusted).await.is_none());
+
+        // a trusted proxy that never sends its header is dropped once the wait is over
+        let (stream, remote, _peer) = connect(b"PROXY TCP4").await;
+        tokio::time::pause();
+        let started = tokio::time::Instant::now();
+        assert!(accept_proxied(stream, remote, &trusted).await.is_none());
+        assert!(started.elapsed() >= PROXY_HEADER_TIMEOUT);
+    }
+}
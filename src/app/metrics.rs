@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use super::client_addr::ClientAddr;
+use super::ip_rules::find_matching_rule;
+use super::state::{AppState, CacheManager};
+
+// the same default buckets as the prometheus client libraries, in seconds
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+// a player counts as active in a realm for this long after their last get or set
+const ACTIVE_PLAYER_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+// set on error responses by ProfileServerError so that the request metrics can count them by variant
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorVariant(pub &'static str);
+
+#[derive(Clone, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(BUCKETS) {
+            if value <= le {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    request_durations: Mutex<BTreeMap<String, Histogram>>,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    db_queries: Mutex<BTreeMap<&'static str, Histogram>>,
+    db_query_failures: Mutex<BTreeMap<&'static str, u64>>,
+    cache_lookups: Mutex<BTreeMap<(&'static str, bool), u64>>,
+    active_players: Mutex<HashMap<String, HashMap<i64, Instant>>>,
+}
+
+impl Metrics {
+    pub fn observe_request(&self, handler: &str, status: StatusCode, elapsed: Duration) {
+        *self.requests.lock().unwrap().entry((handler.to_owned(), status.as_u16())).or_default() += 1;
+        self.request_durations
+            .lock()
+            .unwrap()
+            .entry(handler.to_owned())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_error(&self, variant: &'static str) {
+        *self.errors.lock().unwrap().entry(variant).or_default() += 1;
+    }
+
+    pub fn observe_query(&self, info: &sea_orm::metric::Info<'_>) {
+        // label by the kind of statement rather than the statement itself to keep the series bounded
+        let statement = info.statement.sql.trim_start().get(..6).unwrap_or_default().to_ascii_lowercase();
+        let statement = match statement.as_str() {
+            "select" => "select",
+            "insert" => "insert",
+            "update" => "update",
+            "delete" => "delete",
+            _ => "other",
+        };
+        self.db_queries
+            .lock()
+            .unwrap()
+            .entry(statement)
+            .or_default()
+            .observe(info.elapsed.as_secs_f64());
+        if info.failed {
+            *self.db_query_failures.lock().unwrap().entry(statement).or_default() += 1;
+        }
+    }
+
+    pub fn observe_cache_lookup(&self, cache: &'static str, hit: bool) {
+        *self.cache_lookups.lock().unwrap().entry((cache, hit)).or_default() += 1;
+    }
+
+    pub fn observe_player(&self, realm_name: &str, player_hash: i64) {
+        self.active_players
+            .lock()
+            .unwrap()
+            .entry(realm_name.to_owned())
+            .or_default()
+            .insert(player_hash, Instant::now());
+    }
+
+    fn active_player_counts(&self) -> BTreeMap<String, usize> {
+        let mut active_players = self.active_players.lock().unwrap();
+        // forget the players that have gone quiet while we're at it
+        for players in active_players.values_mut() {
+            players.retain(|_, last_seen| last_seen.elapsed() < ACTIVE_PLAYER_WINDOW);
+        }
+        active_players
+            .iter()
+            .map(|(realm_name, players)| (realm_name.to_owned(), players.len()))
+            .collect()
+    }
+
+    // the prometheus text exposition format
+    pub fn render(&self, cache: &CacheManager) -> Result<String, std::fmt::Error> {
+        let mut out = String::new();
+        header(&mut out, "marshalrwr_http_requests_total", "counter", "Requests handled, by route and status")?;
+        for ((handler, status), count) in self.requests.lock().unwrap().iter() {
+            let labels = format!("handler=\"{}\",status=\"{status}\"", escape(handler));
+            writeln!(out, "marshalrwr_http_requests_total{{{labels}}} {count}")?;
+        }
+        header(&mut out, "marshalrwr_http_request_duration_seconds", "histogram", "Time taken to handle requests, by route")?;
+        for (handler, histogram) in self.request_durations.lock().unwrap().iter() {
+            let labels = format!("handler=\"{}\"", escape(handler));
+            write_histogram(&mut out, "marshalrwr_http_request_duration_seconds", &labels, histogram)?;
+        }
+        header(&mut out, "marshalrwr_profile_server_errors_total", "counter", "Profile server errors, by variant")?;
+        for (variant, count) in self.errors.lock().unwrap().iter() {
+            writeln!(out, "marshalrwr_profile_server_errors_total{{variant=\"{variant}\"}} {count}")?;
+        }
+        header(&mut out, "marshalrwr_db_query_duration_seconds", "histogram", "Time taken by db queries, by statement kind")?;
+        for (statement, histogram) in self.db_queries.lock().unwrap().iter() {
+            let labels = format!("statement=\"{statement}\"");
+            write_histogram(&mut out, "marshalrwr_db_query_duration_seconds", &labels, histogram)?;
+        }
+        header(&mut out, "marshalrwr_db_query_failures_total", "counter", "Failed db queries, by statement kind")?;
+        for (statement, count) in self.db_query_failures.lock().unwrap().iter() {
+            writeln!(out, "marshalrwr_db_query_failures_total{{statement=\"{statement}\"}} {count}")?;
+        }
+        let cache_lookups = self.cache_lookups.lock().unwrap().clone();
+        let caches = cache.entry_counts();
+        for (name, hit, help) in [
+            ("marshalrwr_cache_hits_total", true, "Cache lookups that found an entry"),
+            ("marshalrwr_cache_misses_total", false, "Cache lookups that fell through to the db"),
+        ] {
+            header(&mut out, name, "counter", help)?;
+            for (cache_name, _) in caches {
+                let count = cache_lookups.get(&(cache_name, hit)).copied().unwrap_or_default();
+                writeln!(out, "{name}{{cache=\"{cache_name}\"}} {count}")?;
+            }
+        }
+        header(&mut out, "marshalrwr_cache_entries", "gauge", "Entries currently held in each cache")?;
+        for (cache_name, entries) in caches {
+            writeln!(out, "marshalrwr_cache_entries{{cache=\"{cache_name}\"}} {entries}")?;
+        }
+        header(&mut out, "marshalrwr_realm_active_players", "gauge", "Players seen in each realm in the last 15 minutes")?;
+        for (realm_name, count) in self.active_player_counts() {
+            writeln!(out, "marshalrwr_realm_active_players{{realm=\"{}\"}} {count}", escape(&realm_name))?;
+        }
+        Ok(out)
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> std::fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &str, histogram: &Histogram) -> std::fmt::Result {
+    for (le, count) in BUCKETS.iter().zip(histogram.buckets) {
+        writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {count}")?;
+    }
+    writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", histogram.count)?;
+    writeln!(out, "{name}_sum{{{labels}}} {}", histogram.sum)?;
+    writeln!(out, "{name}_count{{{labels}}} {}", histogram.count)
+}
+
+fn escape(label_value: &str) -> String {
+    label_value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub async fn track_requests<B>(State(state): State<AppState>, request: Request<B>, next: Next<B>) -> Response {
+    // only ever applied as a route layer, so there is always a matched path to go by
+    let handler = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_default();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    state.metrics.observe_request(&handler, response.status(), started.elapsed());
+    if let Some(ErrorVariant(variant)) = response.extensions().get::<ErrorVariant>() {
+        state.metrics.observe_error(variant);
+    }
+    response
+}
+
+pub async fn metrics_handler(ClientAddr(client_ip): ClientAddr, State(state): State<AppState>) -> Response {
+    if find_matching_rule(&state.config().metrics_allowed_ips, client_ip, &state.resolver).is_none() {
+        tracing::warn!("metrics request from '{client_ip}' rejected, not in metrics_allowed_ips");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match state.metrics.render(&state.cache) {
+        Ok(metrics) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics).into_response(),
+        Err(err) => {
+            tracing::error!("failed to render metrics: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_prometheus_text() {
+        let metrics = Metrics::default();
+        metrics.observe_request("/get_profile.php", StatusCode::OK, Duration::from_millis(20));
+        metrics.observe_request("/get_profile.php", StatusCode::OK, Duration::from_millis(300));
+        metrics.observe_request("/get_profile.php", StatusCode::FORBIDDEN, Duration::from_secs(20));
+        metrics.observe_error("Banned");
+        metrics.observe_cache_lookup("players", true);
+        metrics.observe_player("a \"quoted\\\" realm\nname", 1);
+        let out = metrics.render(&CacheManager::default()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        for (name, kind) in [
+            ("marshalrwr_http_requests_total", "counter"),
+            ("marshalrwr_http_request_duration_seconds", "histogram"),
+            ("marshalrwr_profile_server_errors_total", "counter"),
+            ("marshalrwr_db_query_duration_seconds", "histogram"),
+            ("marshalrwr_cache_hits_total", "counter"),
+            ("marshalrwr_cache_entries", "gauge"),
+            ("marshalrwr_realm_active_players", "gauge"),
+        ] {
+            assert!(lines.contains(&format!("# TYPE {name} {kind}").as_str()), "no TYPE line for {name}");
+        }
+        assert!(lines.contains(&r#"marshalrwr_http_requests_total{handler="/get_profile.php",status="200"} 2"#));
+        assert!(lines.contains(&r#"marshalrwr_http_requests_total{handler="/get_profile.php",status="403"} 1"#));
+        assert!(lines.contains(&r#"marshalrwr_profile_server_errors_total{variant="Banned"} 1"#));
+        assert!(lines.contains(&r#"marshalrwr_cache_hits_total{cache="players"} 1"#));
+        assert!(lines.contains(&r#"marshalrwr_cache_misses_total{cache="players"} 0"#));
+        assert!(lines.contains(&r#"marshalrwr_realm_active_players{realm="a \"quoted\\\" realm\nname"} 1"#));
+
+        // the buckets are cumulative and the slowest request only makes it into +Inf
+        let histogram = "marshalrwr_http_request_duration_seconds";
+        let labels = r#"handler="/get_profile.php""#;
+        for (le, count) in [("0.01", 0), ("0.025", 1), ("0.25", 1), ("0.5", 2), ("10", 2), ("+Inf", 3)] {
+            assert!(lines.contains(&format!("{histogram}_bucket{{{labels},le=\"{le}\"}} {count}").as_str()), "le {le}");
+        }
+        assert!(lines.contains(&format!("{histogram}_sum{{{labels}}} 20.32").as_str()));
+        assert!(lines.contains(&format!("{histogram}_count{{{labels}}} 3").as_str()));
+    }
+}
@@ -1,11 +1,24 @@
+pub mod admin;
+pub mod catalog;
+pub mod cli;
+pub mod client_addr;
 pub mod config;
 pub mod errors;
 pub mod hasher;
+pub mod health;
+pub mod ip_rules;
+pub mod listener;
+pub mod metrics;
 pub mod profile_server;
+pub mod reload;
 pub mod signalling;
 pub mod state;
+#[cfg(test)]
+pub mod testing;
 pub mod tracing;
 pub mod validated_query;
+pub mod vanilla;
+pub mod varvaytya;
 
 pub const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 
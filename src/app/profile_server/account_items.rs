@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use sea_orm::{
+    sea_query::Expr, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder,
+};
+
+use super::super::config::ItemStorage;
+use super::errors::ProfileServerError;
+use super::json::{from_blob, to_blob, Blob, EquippedItem, ItemStore, Loadout, StoredItem};
+use entity::{Account, AccountColumn, AccountModel};
+use entity::{AccountItem, AccountItemActiveModel, AccountItemColumn, AccountItemModel};
+
+const CONTAINER_LOADOUT: &str = "loadout";
+const CONTAINER_BACKPACK: &str = "backpack";
+const CONTAINER_STASH: &str = "stash";
+// accounts per query, enough to keep well clear of the bind parameter limits
+const ACCOUNTS_PER_QUERY: usize = 256;
+
+fn accounts_condition(accounts: &[AccountModel]) -> Condition {
+    accounts.iter().fold(Condition::any(), |condition, account| {
+        condition.add(
+            Condition::all()
+                .add(AccountItemColumn::RealmId.eq(account.realm_id))
+                .add(AccountItemColumn::Hash.eq(account.hash)),
+        )
+    })
+}
+
+fn item_row(
+    account: &AccountModel,
+    container: &str,
+    position: usize,
+    (slot, class): (Option<i32>, Option<i32>),
+    index: i32,
+    key: &str,
+    amount: u16,
+) -> AccountItemActiveModel {
+    AccountItemActiveModel {
+        realm_id: ActiveValue::Set(account.realm_id),
+        hash: ActiveValue::Set(account.hash),
+        container: ActiveValue::Set(container.to_owned()),
+        position: ActiveValue::Set(position as i32),
+        slot: ActiveValue::Set(slot),
+        class: ActiveValue::Set(class),
+        item_index: ActiveValue::Set(index),
+        key: ActiveValue::Set(key.to_owned()),
+        amount: ActiveValue::Set(amount as i32),
+    }
+}
+
+fn item_rows(account: &AccountModel) -> Result<Vec<AccountItemActiveModel>, ProfileServerError> {
+    let loadout: Loadout = from_blob(&account.loadout)?;
+    let backpack: ItemStore = from_blob(&account.backpack)?;
+    let stash: ItemStore = from_blob(&account.stash)?;
+    let mut rows = Vec::new();
+    for (position, item) in loadout.slots.iter().enumerate() {
+        let slot_class = (Some(item.slot as i32), None);
+        rows.push(item_row(account, CONTAINER_LOADOUT, position, slot_class, item.index, &item.key, item.amount));
+    }
+    for (container, store) in [(CONTAINER_BACKPACK, &backpack), (CONTAINER_STASH, &stash)] {
+        for (position, item) in store.items.iter().enumerate() {
+            let slot_class = (None, Some(item.class as i32));
+            rows.push(item_row(account, container, position, slot_class, item.index, &item.key, item.amount));
+        }
+    }
+    Ok(rows)
+}
+
+fn fill_containers(account: &mut AccountModel, rows: Vec<AccountItemModel>) -> Result<(), ProfileServerError> {
+    let mut loadout = Loadout { slots: Vec::new() };
+    let mut backpack = ItemStore { items: Vec::new() };
+    let mut stash = ItemStore { items: Vec::new() };
+    // the rows come ordered by container and position, which is the order the game sent them in
+    for row in rows {
+        let (index, amount) = (row.item_index, row.amount as u16);
+        match row.container.as_str() {
+            CONTAINER_LOADOUT => loadout.slots.push(EquippedItem {
+                slot: row.slot.unwrap_or_default() as u8,
+                index,
+                key: row.key,
+                amount,
+            }),
+            container => {
+                let store = match container {
+                    CONTAINER_BACKPACK => &mut backpack,
+                    _ => &mut stash,
+                };
+                store.items.push(StoredItem {
+                    class: row.class.unwrap_or_default() as u8,
+                    index,
+                    key: row.key,
+                    amount,
+                });
+            }
+        }
+    }
+    account.loadout = to_blob(&loadout)?;
+    account.backpack = to_blob(&backpack)?;
+    account.stash = to_blob(&stash)?;
+    Ok(())
+}
+
+// accounts that have item rows take their loadout, backpack and stash from them, the rest keep their json columns
+pub async fn load_account_items(
+    db_conn: &impl ConnectionTrait,
+    accounts: &mut [AccountModel],
+) -> Result<(), ProfileServerError> {
+    for chunk in accounts.chunks_mut(ACCOUNTS_PER_QUERY) {
+        let rows = AccountItem::find()
+            .filter(accounts_condition(chunk))
+            .order_by_asc(AccountItemColumn::RealmId)
+            .order_by_asc(AccountItemColumn::Hash)
+            .order_by_asc(AccountItemColumn::Container)
+            .order_by_asc(AccountItemColumn::Position)
+            .all(db_conn)
+            .await?;
+        if rows.is_empty() {
+            continue;
+        }
+        let mut account_rows: HashMap<(i32, i64), Vec<AccountItemModel>> = HashMap::new();
+        for row in rows {
+            account_rows.entry((row.realm_id, row.hash)).or_default().push(row);
+        }
+        for account in chunk.iter_mut() {
+            if let Some(rows) = account_rows.remove(&(account.realm_id, account.hash)) {
+                fill_containers(account, rows)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// writes the items of the accounts the configured way, returning the account rows to store alongside them
+pub async fn store_account_items(
+    db_conn: &impl ConnectionTrait,
+    accounts: &[AccountModel],
+    storage: ItemStorage,
+) -> Result<Vec<AccountModel>, ProfileServerError> {
+    // json mode never has any rows to replace, see move_account_items for switching between the two
+    if storage == ItemStorage::Json {
+        return Ok(accounts.to_vec());
+    }
+    for chunk in accounts.chunks(ACCOUNTS_PER_QUERY) {
+        AccountItem::delete_many()
+            .filter(accounts_condition(chunk))
+            .exec(db_conn)
+            .await?;
+    }
+    let empty_loadout = to_blob(&Loadout::empty())?;
+    let empty_store = to_blob(&ItemStore::empty())?;
+    let mut stored_accounts = Vec::with_capacity(accounts.len());
+    for account in accounts.iter() {
+        let rows = item_rows(account)?;
+        if !rows.is_empty() {
+            AccountItem::insert_many(rows).exec(db_conn).await?;
+        }
+        stored_accounts.push(AccountModel {
+            loadout: empty_loadout.to_owned(),
+            backpack: empty_store.to_owned(),
+            stash: empty_store.to_owned(),
+            ..account.clone()
+        });
+    }
+    Ok(stored_accounts)
+}
+
+fn has_items(account: &AccountModel) -> Result<bool, ProfileServerError> {
+    let loadout: Loadout = from_blob(&account.loadout)?;
+    let backpack: ItemStore = from_blob(&account.backpack)?;
+    let stash: ItemStore = from_blob(&account.stash)?;
+    Ok(!(loadout.slots.is_empty() && backpack.items.is_empty() && stash.items.is_empty()))
+}
+
+async fn write_containers(db_conn: &impl ConnectionTrait, account: &AccountModel) -> Result<(), DbErr> {
+    Account::update_many()
+        .col_expr(AccountColumn::Loadout, Expr::value(account.loadout.to_owned()))
+        .col_expr(AccountColumn::Backpack, Expr::value(account.backpack.to_owned()))
+        .col_expr(AccountColumn::Stash, Expr::value(account.stash.to_owned()))
+        .filter(AccountColumn::RealmId.eq(account.realm_id))
+        .filter(AccountColumn::Hash.eq(account.hash))
+        .exec(db_conn)
+        .await?;
+    Ok(())
+}
+
+// moves the items of every account over to the given storage, returning how many accounts had items to move,
+// this is done once when switching ps_item_storage so that neither mode has to look after the other's leftovers
+pub async fn move_account_items(
+    db_conn: &impl ConnectionTrait,
+    storage: ItemStorage,
+) -> Result<u64, ProfileServerError> {
+    let mut moved = 0;
+    let mut pages = Account::find()
+        .order_by_asc(AccountColumn::RealmId)
+        .order_by_asc(AccountColumn::Hash)
+        .paginate(db_conn, ACCOUNTS_PER_QUERY as u64);
+    while let Some(mut accounts) = pages.fetch_and_next().await? {
+        match storage {
+            ItemStorage::Json => {
+                let stored_accounts = accounts.clone();
+                load_account_items(db_conn, &mut accounts).await?;
+                for (account, stored_account) in accounts.iter().zip(stored_accounts.iter()) {
+                    if account != stored_account {
+                        write_containers(db_conn, account).await?;
+                        moved += 1;
+                    }
+                }
+            }
+            ItemStorage::Table => {
+                // accounts saved in table mode are left with empty json columns, so only the rest need moving
+                let mut json_accounts = Vec::new();
+                for account in accounts {
+                    if has_items(&account)? {
+                        json_accounts.push(account);
+                    }
+                }
+                for stored_account in store_account_items(db_conn, &json_accounts, storage).await? {
+                    write_containers(db_conn, &stored_account).await?;
+                    moved += 1;
+                }
+            }
+        }
+    }
+    // the rows have all been folded back into the json columns by now
+    if storage == ItemStorage::Json {
+        AccountItem::delete_many().exec(db_conn).await?;
+    }
+    Ok(moved)
+}
+
+pub async fn count_account_items(db_conn: &impl ConnectionTrait) -> Result<u64, DbErr> {
+    AccountItem::find().count(db_conn).await
+}
+
+pub async fn delete_account_items(
+    db_conn: &impl ConnectionTrait,
+    player_hash: i64,
+    realm_id: Option<i32>,
+) -> Result<(), DbErr> {
+    let mut delete = AccountItem::delete_many().filter(AccountItemColumn::Hash.eq(player_hash));
+    if let Some(realm_id) = realm_id {
+        delete = delete.filter(AccountItemColumn::RealmId.eq(realm_id));
+    }
+    delete.exec(db_conn).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::profile_server::util::{get_account_from_db, upsert_account};
+    use crate::app::testing::{account_model, insert_player, insert_realm, test_dbs};
+
+    #[tokio::test]
+    async fn items_only_move_between_storages_when_asked() {
+        for db in test_dbs().await {
+            let realm = insert_realm(&db, "INCURSION").await;
+            insert_player(&db, 1, "FIXTURE").await;
+            insert_player(&db, 2, "RECRUIT").await;
+            let (table_account, json_account) = (account_model(realm.id, 1), account_model(realm.id, 2));
+            upsert_account(&db, &table_account, ItemStorage::Table).await.unwrap();
+            let rows = count_account_items(&db).await.unwrap();
+            assert_eq!(rows, 6);
+
+            // saving in json mode leaves the table alone, whatever is in it
+            upsert_account(&db, &json_account, ItemStorage::Json).await.unwrap();
+            assert_eq!(count_account_items(&db).await.unwrap(), rows);
+
+            assert_eq!(move_account_items(&db, ItemStorage::Json).await.unwrap(), 1);
+            assert_eq!(count_account_items(&db).await.unwrap(), 0);
+            let stored = Account::find_by_id((realm.id, 1)).one(&db).await.unwrap().unwrap();
+            assert_eq!(stored, table_account);
+
+            assert_eq!(move_account_items(&db, ItemStorage::Table).await.unwrap(), 2);
+            assert_eq!(count_account_items(&db).await.unwrap(), rows * 2);
+            let stored = Account::find_by_id((realm.id, 2)).one(&db).await.unwrap().unwrap();
+            assert!(!has_items(&stored).unwrap());
+            for account in [&table_account, &json_account] {
+                let loaded = get_account_from_db(&db, realm.id, account.hash).await.unwrap();
+                assert_eq!(loaded.as_ref(), Some(account));
+            }
+            // everything is in place already
+            assert_eq!(move_account_items(&db, ItemStorage::Table).await.unwrap(), 0);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::net::IpAddr;
+
+use chrono::Utc;
+use sea_orm::{
+    sea_query::Expr, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+use super::super::config::{AnomalyAction, AnomalyRules, AppConfiguration};
+use super::errors::ProfileServerError;
+use super::history::{record_account_history, SOURCE_RELEASE};
+use super::util::upsert_account;
+use entity::AccountModel;
+use entity::{AccountAnomaly, AccountAnomalyActiveModel, AccountAnomalyColumn, AccountAnomalyModel};
+
+const RULE_STAT_REGRESSION: &str = "stat_regression";
+const RULE_AUTHORITY_JUMP: &str = "authority_jump";
+const RULE_KILL_RATE: &str = "kill_rate";
+const RESOLUTION_RELEASED: &str = "released";
+const RESOLUTION_DISMISSED: &str = "dismissed";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnomalyHit {
+    pub rule: String,
+    pub action: AnomalyAction,
+    pub detail: String,
+}
+
+// the lifetime counters that the game only ever adds to
+fn counters(account: &AccountModel) -> [(&'static str, f64); 14] {
+    [
+        ("max_authority_reached", account.max_authority_reached),
+        ("kills", account.kills as f64),
+        ("deaths", account.deaths as f64),
+        ("time_played", account.time_played as f64),
+        ("player_kills", account.player_kills as f64),
+        ("teamkills", account.teamkills as f64),
+        ("longest_kill_streak", account.longest_kill_streak as f64),
+        ("targets_destroyed", account.targets_destroyed as f64),
+        ("vehicles_destroyed", account.vehicles_destroyed as f64),
+        ("soldiers_healed", account.soldiers_healed as f64),
+        ("distance_moved", account.distance_moved),
+        ("shots_fired", account.shots_fired as f64),
+        ("throwables_thrown", account.throwables_thrown as f64),
+        ("longest_death_streak", account.longest_death_streak as f64),
+    ]
+}
+
+pub fn check_account(rules: &AnomalyRules, stored: &AccountModel, incoming: &AccountModel) -> Vec<AnomalyHit> {
+    let mut hits = Vec::new();
+    if rules.stat_regression != AnomalyAction::Off {
+        let regressions: Vec<String> = counters(stored)
+            .iter()
+            .zip(counters(incoming).iter())
+            .filter(|((_, before), (_, after))| after < before)
+            .map(|((name, before), (_, after))| format!("{name} {before} -> {after}"))
+            .collect();
+        if !regressions.is_empty() {
+            hits.push(AnomalyHit {
+                rule: String::from(RULE_STAT_REGRESSION),
+                action: rules.stat_regression,
+                detail: regressions.join(", "),
+            });
+        }
+    }
+    if rules.authority_jump != AnomalyAction::Off {
+        let gain = incoming.authority - stored.authority;
+        if gain > rules.max_authority_gain {
+            hits.push(AnomalyHit {
+                rule: String::from(RULE_AUTHORITY_JUMP),
+                action: rules.authority_jump,
+                detail: format!(
+                    "authority {} -> {} (+{gain:.2}, max +{})",
+                    stored.authority, incoming.authority, rules.max_authority_gain
+                ),
+            });
+        }
+    }
+    if rules.kill_rate != AnomalyAction::Off {
+        let kills = incoming.kills - stored.kills;
+        let hours = (incoming.time_played - stored.time_played) as f64 / 3600.0;
+        // allow at least a minute's worth of kills so that saves made close together aren't flagged
+        if kills as f64 > rules.max_kills_per_hour * hours.max(1.0 / 60.0) {
+            hits.push(AnomalyHit {
+                rule: String::from(RULE_KILL_RATE),
+                action: rules.kill_rate,
+                detail: format!(
+                    "{kills} kills in {:.1} minutes played (max {} per hour)",
+                    hours * 60.0,
+                    rules.max_kills_per_hour
+                ),
+            });
+        }
+    }
+    hits
+}
+
+pub fn most_severe(hits: &[AnomalyHit]) -> AnomalyAction {
+    hits.iter().map(|hit| hit.action).max().unwrap_or_default()
+}
+
+pub fn describe_hits(hits: &[AnomalyHit]) -> String {
+    hits.iter()
+        .map(|hit| format!("{} [{}]: {}", hit.rule, hit.action.as_str(), hit.detail))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+pub async fn record_anomaly(
+    db_conn: &impl ConnectionTrait,
+    account: &AccountModel,
+    hits: &[AnomalyHit],
+    source_ip: Option<IpAddr>,
+) -> Result<(), ProfileServerError> {
+    let anomaly = AccountAnomalyActiveModel {
+        realm_id: ActiveValue::Set(account.realm_id),
+        hash: ActiveValue::Set(account.hash),
+        created_at: ActiveValue::Set(Utc::now()),
+        action: ActiveValue::Set(most_severe(hits).as_str().to_owned()),
+        hits: ActiveValue::Set(serde_json::to_string(hits)?),
+        // the incoming save is kept whatever the action, it is the evidence (or what gets released)
+        snapshot: ActiveValue::Set(serde_json::to_string(account)?),
+        source_ip: ActiveValue::Set(source_ip.map(|ip| ip.to_string())),
+        ..Default::default()
+    };
+    AccountAnomaly::insert(anomaly).exec(db_conn).await?;
+    Ok(())
+}
+
+pub async fn list_anomalies(
+    db_conn: &impl ConnectionTrait,
+    realm_id: Option<i32>,
+    include_resolved: bool,
+    offset: u64,
+    limit: u64,
+) -> Result<(Vec<AccountAnomalyModel>, u64), ProfileServerError> {
+    let mut query = AccountAnomaly::find().order_by_desc(AccountAnomalyColumn::Id);
+    if let Some(realm_id) = realm_id {
+        query = query.filter(AccountAnomalyColumn::RealmId.eq(realm_id));
+    }
+    if !include_resolved {
+        query = query.filter(AccountAnomalyColumn::Resolution.is_null());
+    }
+    let total = query.clone().count(db_conn).await?;
+    let anomalies = query.offset(offset).limit(limit).all(db_conn).await?;
+    Ok((anomalies, total))
+}
+
+pub async fn get_anomaly(
+    db_conn: &impl ConnectionTrait,
+    anomaly_id: i32,
+) -> Result<Option<AccountAnomalyModel>, ProfileServerError> {
+    Ok(AccountAnomaly::find_by_id(anomaly_id).one(db_conn).await?)
+}
+
+pub fn anomaly_hits(anomaly: &AccountAnomalyModel) -> Result<Vec<AnomalyHit>, ProfileServerError> {
+    Ok(serde_json::from_str(&anomaly.hits)?)
+}
+
+pub fn anomaly_account(anomaly: &AccountAnomalyModel) -> Result<AccountModel, ProfileServerError> {
+    Ok(serde_json::from_str(&anomaly.snapshot)?)
+}
+
+// only quarantined saves are held back for release, warned saves already went through and rejected ones stay rejected
+pub fn is_releasable(anomaly: &AccountAnomalyModel) -> bool {
+    anomaly.resolution.is_none() && anomaly.action == AnomalyAction::Quarantine.as_str()
+}
+
+async fn resolve_anomaly(
+    db_conn: &impl ConnectionTrait,
+    anomaly: &AccountAnomalyModel,
+    resolution: &str,
+    actor: &str,
+) -> Result<AccountAnomalyModel, ProfileServerError> {
+    // only resolve it if nobody else got there first, the admin api and the cli can race each other
+    let result = AccountAnomaly::update_many()
+        .col_expr(AccountAnomalyColumn::Resolution, Expr::value(resolution))
+        .col_expr(AccountAnomalyColumn::ResolvedAt, Expr::value(Utc::now()))
+        .col_expr(AccountAnomalyColumn::ResolvedBy, Expr::value(actor))
+        .filter(AccountAnomalyColumn::Id.eq(anomaly.id))
+        .filter(AccountAnomalyColumn::Resolution.is_null())
+        .exec(db_conn)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(ProfileServerError::AnomalyAlreadyResolved(anomaly.id));
+    }
+    get_anomaly(db_conn, anomaly.id)
+        .await?
+        .ok_or(ProfileServerError::AnomalyAlreadyResolved(anomaly.id))
+}
+
+pub async fn release_anomaly(
+    db_conn: &DatabaseConnection,
+    anomaly: &AccountAnomalyModel,
+    actor: &str,
+    app_config: &AppConfiguration,
+) -> Result<AccountModel, ProfileServerError> {
+    let account = anomaly_account(anomaly)?;
+    let txn = db_conn.begin().await?;
+    // claim the anomaly first, if it was resolved in the meantime nothing is written
+    resolve_anomaly(&txn, anomaly, RESOLUTION_RELEASED, actor).await?;
+    record_account_history(
+        &txn,
+        account.realm_id,
+        std::slice::from_ref(&account),
+        SOURCE_RELEASE,
+        None,
+        app_config.ps_account_history_limit,
+    )
+    .await?;
+    upsert_account(&txn, &account, app_config.ps_item_storage).await?;
+    txn.commit().await?;
+    Ok(account)
+}
+
+pub async fn dismiss_anomaly(
+    db_conn: &DatabaseConnection,
+    anomaly: &AccountAnomalyModel,
+    actor: &str,
+) -> Result<AccountAnomalyModel, ProfileServerError> {
+    resolve_anomaly(db_conn, anomaly, RESOLUTION_DISMISSED, actor).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::profile_server::util::get_account_from_db;
+    use crate::app::testing::{account_model, insert_player, insert_realm, test_dbs};
+
+    #[tokio::test]
+    async fn anomalies_are_only_resolved_once() {
+        for db in test_dbs().await {
+            let realm = insert_realm(&db, "INCURSION").await;
+            insert_player(&db, 1234, "FIXTURE").await;
+            let hits = [AnomalyHit {
+                rule: String::from(RULE_AUTHORITY_JUMP),
+                action: AnomalyAction::Quarantine,
+                detail: String::from("authority 0 -> 100"),
+            }];
+            record_anomaly(&db, &account_model(realm.id, 1234), &hits, None).await.unwrap();
+            let (anomalies, _) = list_anomalies(&db, None, false, 0, 10).await.unwrap();
+            let anomaly = &anomalies[0];
+
+            let dismissed = dismiss_anomaly(&db, anomaly, "cli").await.unwrap();
+            assert_eq!(dismissed.resolution.as_deref(), Some(RESOLUTION_DISMISSED));
+
+            // a release with the stale copy loses the race, and takes its writes with it
+            let err = release_anomaly(&db, anomaly, "admin", &AppConfiguration::default()).await.unwrap_err();
+            assert!(matches!(err, ProfileServerError::AnomalyAlreadyResolved(id) if id == anomaly.id));
+            assert_eq!(get_account_from_db(&db, realm.id, 1234).await.unwrap(), None);
+            let err = dismiss_anomaly(&db, anomaly, "admin").await.unwrap_err();
+            assert!(matches!(err, ProfileServerError::AnomalyAlreadyResolved(_)));
+            let anomaly = get_anomaly(&db, anomaly.id).await.unwrap().unwrap();
+            assert_eq!(anomaly.resolved_by.as_deref(), Some("cli"));
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, EntityTrait, IntoActiveModel,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+
+use super::super::config::RealmConfiguration;
+use super::super::hasher::rwr1_hash_username;
+use super::super::ip_rules::IpRule;
+use super::super::state::AppState;
+use super::errors::ProfileServerError;
+use entity::{Ban, BanActiveModel, BanColumn, BanModel};
+
+pub const BAN_SID: &str = "sid";
+pub const BAN_HASH: &str = "hash";
+pub const BAN_IP: &str = "ip";
+
+// what a ban applies to, players are banned by hash so that a username can be banned before it ever enlists
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BanTarget {
+    Sid(i64),
+    Hash(i64),
+    Ip(IpRule),
+}
+
+impl BanTarget {
+    pub fn username(username: &str) -> Self {
+        BanTarget::Hash(rwr1_hash_username(username))
+    }
+
+    pub fn ip(ip: &str) -> Result<Self, String> {
+        // hostnames resolve to whatever they like, bans only take fixed addresses and networks
+        match ip.parse::<IpRule>()? {
+            IpRule::Host(host) => Err(format!("'{host}' is a hostname, bans take an ip address or cidr block")),
+            rule => Ok(BanTarget::Ip(rule)),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BanTarget::Sid(_) => BAN_SID,
+            BanTarget::Hash(_) => BAN_HASH,
+            BanTarget::Ip(_) => BAN_IP,
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match self {
+            BanTarget::Sid(sid) => sid.to_string(),
+            BanTarget::Hash(hash) => hash.to_string(),
+            BanTarget::Ip(rule) => rule.to_string(),
+        }
+    }
+
+    fn of(ban: &BanModel) -> Option<Self> {
+        match ban.kind.as_str() {
+            BAN_SID => ban.target.parse().ok().map(BanTarget::Sid),
+            BAN_HASH => ban.target.parse().ok().map(BanTarget::Hash),
+            BAN_IP => ban.target.parse().ok().map(BanTarget::Ip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} '{}'", self.kind(), self.value())
+    }
+}
+
+// a ban is in force until it is lifted or it expires, whichever comes first
+pub fn is_active(ban: &BanModel, now: DateTime<Utc>) -> bool {
+    ban.lifted_at.is_none() && ban.expires_at.is_none_or(|expires_at| expires_at > now)
+}
+
+fn active_condition(now: DateTime<Utc>) -> Condition {
+    Condition::all().add(BanColumn::LiftedAt.is_null()).add(
+        Condition::any()
+            .add(BanColumn::ExpiresAt.is_null())
+            .add(BanColumn::ExpiresAt.gt(now)),
+    )
+}
+
+pub async fn create_ban(
+    db_conn: &impl ConnectionTrait,
+    target: &BanTarget,
+    realm: Option<&str>,
+    reason: &str,
+    issued_by: &str,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<BanModel, ProfileServerError> {
+    let ban = BanActiveModel {
+        kind: ActiveValue::Set(target.kind().to_owned()),
+        target: ActiveValue::Set(target.value()),
+        realm: ActiveValue::Set(realm.map(str::to_owned)),
+        reason: ActiveValue::Set(reason.to_owned()),
+        issued_by: ActiveValue::Set(issued_by.to_owned()),
+        created_at: ActiveValue::Set(Utc::now()),
+        expires_at: ActiveValue::Set(expires_at),
+        ..Default::default()
+    };
+    Ok(ban.insert(db_conn).await?)
+}
+
+pub async fn lift_ban(
+    db_conn: &impl ConnectionTrait,
+    ban: &BanModel,
+    actor: &str,
+) -> Result<BanModel, ProfileServerError> {
+    let mut ban = ban.clone().into_active_model();
+    ban.lifted_at = ActiveValue::Set(Some(Utc::now()));
+    ban.lifted_by = ActiveValue::Set(Some(actor.to_owned()));
+    Ok(ban.update(db_conn).await?)
+}
+
+pub async fn get_ban(db_conn: &impl ConnectionTrait, ban_id: i32) -> Result<Option<BanModel>, ProfileServerError> {
+    Ok(Ban::find_by_id(ban_id).one(db_conn).await?)
+}
+
+pub async fn list_bans(
+    db_conn: &impl ConnectionTrait,
+    include_inactive: bool,
+    offset: u64,
+    limit: u64,
+) -> Result<(Vec<BanModel>, u64), ProfileServerError> {
+    let mut query = Ban::find().order_by_desc(BanColumn::Id);
+    if !include_inactive {
+        query = query.filter(active_condition(Utc::now()));
+    }
+    let total = query.clone().count(db_conn).await?;
+    let bans = query.offset(offset).limit(limit).all(db_conn).await?;
+    Ok((bans, total))
+}
+
+async fn active_bans(state: &AppState) -> Result<Arc<Vec<BanModel>>, ProfileServerError> {
+    let cached = state.cache.bans.get(&());
+    state.metrics.observe_cache_lookup("bans", cached.is_some());
+    if let Some(bans) = cached {
+        return Ok(bans);
+    }
+    let bans = Arc::new(Ban::find().filter(active_condition(Utc::now())).all(&state.db).await?);
+    state.cache.bans.insert((), bans.clone()).await;
+    Ok(bans)
+}
+
+async fn check_bans(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    is_banned: impl Fn(&BanTarget) -> bool,
+) -> Result<(), ProfileServerError> {
+    let now = Utc::now();
+    let bans = active_bans(state).await?;
+    // the cached bans are re-checked for expiry, so that they lapse on time rather than with the cache
+    let ban = bans.iter().find(|ban| {
+        is_active(ban, now)
+            && ban.realm.as_ref().is_none_or(|realm| realm == &realm_config.name)
+            && BanTarget::of(ban).is_some_and(|target| is_banned(&target))
+    });
+    match ban {
+        Some(ban) => Err(ProfileServerError::Banned(
+            BanTarget::of(ban).map(|target| target.to_string()).unwrap_or_default(),
+            ban.reason.to_owned(),
+        )),
+        None => Ok(()),
+    }
+}
+
+pub async fn check_ip_bans(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    ip: IpAddr,
+) -> Result<(), ProfileServerError> {
+    check_bans(state, realm_config, |target| match target {
+        BanTarget::Ip(rule) => rule.matches(ip.to_canonical(), &state.resolver),
+        _ => false,
+    })
+    .await
+}
+
+pub async fn check_player_bans(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    player_hash: i64,
+    sid: i64,
+) -> Result<(), ProfileServerError> {
+    check_bans(state, realm_config, |target| match target {
+        BanTarget::Sid(banned_sid) => *banned_sid == sid,
+        BanTarget::Hash(banned_hash) => *banned_hash == player_hash,
+        BanTarget::Ip(_) => false,
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::app::config::AppConfiguration;
+    use crate::app::testing::sqlite_db;
+
+    fn realm(name: &str) -> RealmConfiguration {
+        RealmConfiguration { name: name.to_owned(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn bans_apply_to_their_targets_and_realms_until_they_expire() {
+        let state = AppState::new(AppConfiguration::default(), sqlite_db().await, None);
+        let hash = rwr1_hash_username("BANNED");
+        let ip = IpAddr::from([198, 51, 100, 7]);
+        let expired = Some(Utc::now() - Duration::hours(1));
+        // the bans are all in place before the first check, which caches them
+        create_ban(&state.db, &BanTarget::username("BANNED"), None, "cheating", "test", None).await.unwrap();
+        create_ban(&state.db, &BanTarget::Sid(7), Some("CLAN"), "griefing", "test", None).await.unwrap();
+        create_ban(&state.db, &BanTarget::Sid(8), None, "served", "test", expired).await.unwrap();
+        create_ban(&state.db, &BanTarget::ip("198.51.100.0/24").unwrap(), Some("CLAN"), "vpn", "test", None)
+            .await
+            .unwrap();
+        create_ban(&state.db, &BanTarget::ip("203.0.113.7").unwrap(), None, "served", "test", expired).await.unwrap();
+
+        // banned by username before ever enlisting, whatever the sid
+        let err = check_player_bans(&state, &realm("INCURSION"), hash, 1).await.unwrap_err();
+        let ProfileServerError::Banned(target, reason) = err else { panic!("not a ban: {err}") };
+        assert_eq!((target, reason.as_str()), (format!("hash '{hash}'"), "cheating"));
+        assert!(check_player_bans(&state, &realm("INCURSION"), rwr1_hash_username("INNOCENT"), 1).await.is_ok());
+
+        // realm bans stay in their realm
+        assert!(check_player_bans(&state, &realm("CLAN"), 1, 7).await.is_err());
+        assert!(check_player_bans(&state, &realm("INCURSION"), 1, 7).await.is_ok());
+        assert!(check_ip_bans(&state, &realm("CLAN"), ip).await.is_err());
+        assert!(check_ip_bans(&state, &realm("INCURSION"), ip).await.is_ok());
+        assert!(check_ip_bans(&state, &realm("CLAN"), IpAddr::from([198, 51, 101, 7])).await.is_ok());
+
+        // expired bans are over
+        assert!(check_player_bans(&state, &realm("INCURSION"), 1, 8).await.is_ok());
+        assert!(check_ip_bans(&state, &realm("INCURSION"), IpAddr::from([203, 0, 113, 7])).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn cached_bans_lapse_on_time() {
+        let state = AppState::new(AppConfiguration::default(), sqlite_db().await, None);
+        let expires_at = Some(Utc::now() + Duration::milliseconds(200));
+        create_ban(&state.db, &BanTarget::Sid(7), None, "cooling off", "test", expires_at).await.unwrap();
+        assert!(check_player_bans(&state, &realm("INCURSION"), 1, 7).await.is_err());
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert!(check_player_bans(&state, &realm("INCURSION"), 1, 7).await.is_ok());
+    }
+}
@@ -16,6 +16,7 @@ use sea_orm::error::DbErr;
 use thiserror::Error;
 use validator::ValidationErrors;
 
+use super::super::metrics::ErrorVariant;
 use super::util::HEADERS;
 
 #[derive(Debug, Error)]
@@ -40,8 +41,16 @@ pub enum ProfileServerError {
     SerdeJsonError(#[from] serde_json::Error),
     #[error("ip address '{0}' not allowed to get/set")]
     ClientAddressNotAllowed(IpAddr),
+    #[error("client address could not be determined: {0}")]
+    ClientAddressUnknown(String),
     #[error("realm '{0}' is not configured")]
     RealmNotConfigured(String),
+    #[error("realm '{0}' has not been seeded and strict mode is on")]
+    RealmNotSeeded(String),
+    #[error("realm '{0}' is read-only")]
+    RealmReadOnly(String),
+    #[error("realm '{0}' is full ({1} players)")]
+    RealmFull(String, u64),
     #[error("sid '{0}' not allowed by config")]
     SidNotAllowed(i64),
     #[error("sid '{0}' blocked by config")]
@@ -54,9 +63,57 @@ pub enum ProfileServerError {
     PlayerRidIncorrect(i64, String, i64, String),
     #[error("player '{1}' [hash:{0}, sid:{2}] not found in db")]
     PlayerNotFound(i64, String, i64),
+    #[error("player hash {0} is not the hash of username '{1}' ({2})")]
+    PlayerHashMismatch(i64, String, i64),
+    #[error("player '{1}' [hash:{0}] rid differs between player and profile")]
+    PlayerRidMismatch(i64, String),
+    #[error("account json schema version {0} is not supported by this version of marshalrwr")]
+    JsonSchemaUnsupported(String),
+    #[error("too many requests from {0}, slow down")]
+    RateLimited(String),
+    #[error("too many new players enlisting in realm '{0}', slow down")]
+    EnlistmentRateLimited(String),
+    #[error("{0} is banned: {1}")]
+    Banned(String, String),
+    #[error("anomaly {0} has already been resolved")]
+    AnomalyAlreadyResolved(i32),
 }
 
 impl ProfileServerError {
+    // the name of the variant, for labelling metrics with
+    pub fn variant(&self) -> &'static str {
+        match self {
+            ProfileServerError::ValidationError(_) => "ValidationError",
+            ProfileServerError::AxumQueryRejection(_) => "AxumQueryRejection",
+            ProfileServerError::AxumBytesRejection(_) => "AxumBytesRejection",
+            ProfileServerError::SeaOrmDbError(_) => "SeaOrmDbError",
+            ProfileServerError::QuickXmlError(_) => "QuickXmlError",
+            ProfileServerError::QuickXmlDeserializationFailed(_) => "QuickXmlDeserializationFailed",
+            ProfileServerError::Utf8Error(_) => "Utf8Error",
+            ProfileServerError::FromUtf8Error(_) => "FromUtf8Error",
+            ProfileServerError::SerdeJsonError(_) => "SerdeJsonError",
+            ProfileServerError::ClientAddressNotAllowed(_) => "ClientAddressNotAllowed",
+            ProfileServerError::ClientAddressUnknown(_) => "ClientAddressUnknown",
+            ProfileServerError::RealmNotConfigured(_) => "RealmNotConfigured",
+            ProfileServerError::RealmNotSeeded(_) => "RealmNotSeeded",
+            ProfileServerError::RealmReadOnly(_) => "RealmReadOnly",
+            ProfileServerError::RealmFull(_, _) => "RealmFull",
+            ProfileServerError::SidNotAllowed(_) => "SidNotAllowed",
+            ProfileServerError::SidBlocked(_) => "SidBlocked",
+            ProfileServerError::RealmDigestIncorrect(_, _) => "RealmDigestIncorrect",
+            ProfileServerError::PlayerSidMismatch(_, _, _, _) => "PlayerSidMismatch",
+            ProfileServerError::PlayerRidIncorrect(_, _, _, _) => "PlayerRidIncorrect",
+            ProfileServerError::PlayerNotFound(_, _, _) => "PlayerNotFound",
+            ProfileServerError::PlayerHashMismatch(_, _, _) => "PlayerHashMismatch",
+            ProfileServerError::PlayerRidMismatch(_, _) => "PlayerRidMismatch",
+            ProfileServerError::JsonSchemaUnsupported(_) => "JsonSchemaUnsupported",
+            ProfileServerError::RateLimited(_) => "RateLimited",
+            ProfileServerError::EnlistmentRateLimited(_) => "EnlistmentRateLimited",
+            ProfileServerError::Banned(_, _) => "Banned",
+            ProfileServerError::AnomalyAlreadyResolved(_) => "AnomalyAlreadyResolved",
+        }
+    }
+
     pub fn to_xml_string(&self) -> String {
         let mut error_data_xml_writer = Writer::new(Cursor::new(Vec::new()));
         let mut data_element_start = BytesStart::new("data");
@@ -73,13 +130,24 @@ impl ProfileServerError {
             ProfileServerError::FromUtf8Error(err) => err.to_string(),
             ProfileServerError::SerdeJsonError(err) => err.to_string(),
             ProfileServerError::ClientAddressNotAllowed(_) => self.to_string(),
+            ProfileServerError::ClientAddressUnknown(_) => self.to_string(),
             ProfileServerError::RealmNotConfigured(_) => self.to_string(),
+            ProfileServerError::RealmNotSeeded(_) => self.to_string(),
+            ProfileServerError::RealmReadOnly(_) => self.to_string(),
+            ProfileServerError::RealmFull(_, _) => self.to_string(),
             ProfileServerError::SidNotAllowed(_) => self.to_string(),
             ProfileServerError::SidBlocked(_) => self.to_string(),
             ProfileServerError::RealmDigestIncorrect(_, _) => self.to_string(),
             ProfileServerError::PlayerSidMismatch(_, _, _, _) => self.to_string(),
             ProfileServerError::PlayerRidIncorrect(_, _, _, _) => self.to_string(),
             ProfileServerError::PlayerNotFound(_, _, _) => self.to_string(),
+            ProfileServerError::PlayerHashMismatch(_, _, _) => self.to_string(),
+            ProfileServerError::PlayerRidMismatch(_, _) => self.to_string(),
+            ProfileServerError::JsonSchemaUnsupported(_) => self.to_string(),
+            ProfileServerError::RateLimited(_) => self.to_string(),
+            ProfileServerError::EnlistmentRateLimited(_) => self.to_string(),
+            ProfileServerError::Banned(_, _) => self.to_string(),
+            ProfileServerError::AnomalyAlreadyResolved(_) => self.to_string(),
         };
         // escape the message :D
         let escaped_msg = escape(&msg).to_string();
@@ -100,7 +168,8 @@ impl ProfileServerError {
 impl IntoResponse for ProfileServerError {
     fn into_response(self) -> Response {
         tracing::error!("{}", self.to_string());
-        match self {
+        let variant = ErrorVariant(self.variant());
+        let mut response = match self {
             ProfileServerError::ValidationError(_) => {
                 (StatusCode::BAD_REQUEST, HEADERS, self.to_xml_string())
             }
@@ -141,9 +210,21 @@ impl IntoResponse for ProfileServerError {
             ProfileServerError::ClientAddressNotAllowed(_) => {
                 (StatusCode::UNAUTHORIZED, HEADERS, self.to_xml_string())
             }
+            ProfileServerError::ClientAddressUnknown(_) => {
+                (StatusCode::BAD_REQUEST, HEADERS, self.to_xml_string())
+            }
             ProfileServerError::RealmNotConfigured(_) => {
                 (StatusCode::BAD_REQUEST, HEADERS, self.to_xml_string())
             }
+            ProfileServerError::RealmNotSeeded(_) => {
+                (StatusCode::UNAUTHORIZED, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::RealmReadOnly(_) => {
+                (StatusCode::FORBIDDEN, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::RealmFull(_, _) => {
+                (StatusCode::FORBIDDEN, HEADERS, self.to_xml_string())
+            }
             ProfileServerError::SidNotAllowed(_) => {
                 (StatusCode::FORBIDDEN, HEADERS, self.to_xml_string())
             }
@@ -162,7 +243,32 @@ impl IntoResponse for ProfileServerError {
             ProfileServerError::PlayerNotFound(_, _, _) => {
                 (StatusCode::BAD_REQUEST, HEADERS, self.to_xml_string())
             }
+            ProfileServerError::PlayerHashMismatch(_, _, _) => {
+                (StatusCode::BAD_REQUEST, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::PlayerRidMismatch(_, _) => {
+                (StatusCode::BAD_REQUEST, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::JsonSchemaUnsupported(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                HEADERS,
+                self.to_xml_string(),
+            ),
+            ProfileServerError::RateLimited(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::EnlistmentRateLimited(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::Banned(_, _) => {
+                (StatusCode::FORBIDDEN, HEADERS, self.to_xml_string())
+            }
+            ProfileServerError::AnomalyAlreadyResolved(_) => {
+                (StatusCode::CONFLICT, HEADERS, self.to_xml_string())
+            }
         }
-        .into_response()
+        .into_response();
+        response.extensions_mut().insert(variant);
+        response
     }
 }
@@ -0,0 +1,292 @@
+use std::io::Cursor;
+
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+
+use super::errors::ProfileServerError;
+use super::json::Blob;
+use super::xml::SetProfileDataXml;
+
+// everything the xml structs model, anything else on person, profile or stats is kept as an extra
+const PERSON_ATTRIBUTES: [&str; 9] = [
+    "max_authority_reached",
+    "authority",
+    "job_points",
+    "faction",
+    "name",
+    "version",
+    "soldier_group_id",
+    "soldier_group_name",
+    "squad_size_setting",
+];
+const PERSON_CHILDREN: [&str; 3] = ["item", "backpack", "stash"];
+const PROFILE_ATTRIBUTES: [&str; 5] = ["game_version", "username", "sid", "rid", "squad_tag"];
+const PROFILE_CHILDREN: [&str; 1] = ["stats"];
+const STATS_ATTRIBUTES: [&str; 13] = [
+    "kills",
+    "deaths",
+    "time_played",
+    "player_kills",
+    "teamkills",
+    "longest_kill_streak",
+    "targets_destroyed",
+    "vehicles_destroyed",
+    "soldiers_healed",
+    "distance_moved",
+    "shots_fired",
+    "throwables_thrown",
+    "rank_progression",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ElementExtras {
+    #[serde(rename = "a", default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<(String, String)>,
+    // each child element exactly as it was sent
+    #[serde(rename = "c", default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<String>,
+}
+
+impl ElementExtras {
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.children.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountExtras {
+    #[serde(default, skip_serializing_if = "ElementExtras::is_empty")]
+    pub person: ElementExtras,
+    #[serde(default, skip_serializing_if = "ElementExtras::is_empty")]
+    pub profile: ElementExtras,
+    #[serde(default, skip_serializing_if = "ElementExtras::is_empty")]
+    pub stats: ElementExtras,
+    // the positions of the monitors kept whole in stats.children, which the account columns must then skip
+    #[serde(skip)]
+    pub raw_monitors: Vec<usize>,
+}
+
+impl Blob for AccountExtras {
+    fn empty() -> Self {
+        Self::default()
+    }
+}
+
+impl AccountExtras {
+    pub fn is_empty(&self) -> bool {
+        self.person.is_empty() && self.profile.is_empty() && self.stats.is_empty()
+    }
+
+    fn element(&self, name: &[u8]) -> Option<&ElementExtras> {
+        match name {
+            b"person" => Some(&self.person),
+            b"profile" => Some(&self.profile),
+            b"stats" => Some(&self.stats),
+            _ => None,
+        }
+    }
+}
+
+// bodies that hang on to the parts of the xml their structs don't model
+pub trait CaptureExtras {
+    fn capture_extras(&mut self, xml: &str) -> Result<(), ProfileServerError>;
+}
+
+impl CaptureExtras for SetProfileDataXml {
+    fn capture_extras(&mut self, xml: &str) -> Result<(), ProfileServerError> {
+        // the players come out of the xml in the same order that they were deserialized in
+        for (player, extras) in self.players.iter_mut().zip(capture_extras(xml)?) {
+            player.extras = extras;
+        }
+        Ok(())
+    }
+}
+
+fn name_of(bytes: &[u8]) -> Result<String, ProfileServerError> {
+    Ok(std::str::from_utf8(bytes)?.to_owned())
+}
+
+fn unknown_attributes(element: &BytesStart, known: &[&str]) -> Result<Vec<(String, String)>, ProfileServerError> {
+    let mut attributes = Vec::new();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        let key = name_of(attribute.key.as_ref())?;
+        if !known.contains(&key.as_str()) {
+            attributes.push((key, attribute.unescape_value()?.into_owned()));
+        }
+    }
+    Ok(attributes)
+}
+
+// whether a monitor is fully covered by the longest_death_streak, kill_combos and criteria_monitors columns
+fn is_modelled_monitor(monitor: &str) -> Result<bool, ProfileServerError> {
+    let mut reader = Reader::from_str(monitor);
+    let mut children: &[&str] = &[];
+    let mut depth = 0;
+    loop {
+        let (element, is_empty) = match reader.read_event()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(_) => {
+                depth -= 1;
+                continue;
+            }
+            Event::Text(text) if !text.unescape()?.trim().is_empty() => return Ok(false),
+            Event::Eof => return Ok(true),
+            _ => continue,
+        };
+        let attributes: &[&str] = match (depth, element.name().as_ref()) {
+            (0, _) => {
+                let name = element.try_get_attribute("name")?;
+                let (attributes, allowed_children): (&[&str], &[&str]) = match name {
+                    Some(name) => match name.unescape_value()?.as_ref() {
+                        "kill combo" => (&["name"], &["entry"]),
+                        "death streak" => (&["name", "longest_death_streak"], &[]),
+                        _ => (&["name", "level"], &["criteria"]),
+                    },
+                    // nameless monitors are skipped, which is only harmless when they are empty
+                    None => (&[], &[]),
+                };
+                children = allowed_children;
+                attributes
+            }
+            (1, b"entry") if children.contains(&"entry") => &["combo", "count"],
+            (1, b"criteria") if children.contains(&"criteria") => &["count"],
+            _ => return Ok(false),
+        };
+        if !unknown_attributes(&element, attributes)?.is_empty() || (depth == 1 && !is_empty) {
+            return Ok(false);
+        }
+        if !is_empty {
+            depth += 1;
+        }
+    }
+}
+
+// splits the unmodelled attributes and child elements off of every player's person, profile and stats, in order,
+// a document without any player elements (i.e. a vanilla .person or .profile file) counts as a single player
+pub fn capture_extras(xml: &str) -> Result<Vec<AccountExtras>, ProfileServerError> {
+    let mut reader = Reader::from_str(xml);
+    let mut accounts: Vec<AccountExtras> = Vec::new();
+    // the element we are in, only person, profile and stats (and anything above them) are ever entered
+    let mut path: Vec<String> = Vec::new();
+    let mut monitor_position = 0;
+    loop {
+        let position = reader.buffer_position();
+        let (element, is_empty) = match reader.read_event()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(_) => {
+                path.pop();
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let name = name_of(element.name().as_ref())?;
+        let parent = path.last().map(String::as_str);
+        let is_account = name == "player" || (accounts.is_empty() && (name == "person" || name == "profile"));
+        if is_account {
+            accounts.push(AccountExtras::default());
+        }
+        let Some(account) = accounts.last_mut() else {
+            if !is_empty {
+                path.push(name);
+            }
+            continue;
+        };
+        let (extras, known) = match (parent, name.as_str()) {
+            (_, "person") => (&mut account.person, PERSON_ATTRIBUTES.as_slice()),
+            (_, "profile") => (&mut account.profile, PROFILE_ATTRIBUTES.as_slice()),
+            (Some("profile"), "stats") => {
+                monitor_position = 0;
+                (&mut account.stats, STATS_ATTRIBUTES.as_slice())
+            }
+            (Some(parent @ ("person" | "profile" | "stats")), _) => {
+                // a child of one of our elements, skip past it while taking note of the raw xml if it's unknown
+                if !is_empty {
+                    reader.read_to_end(element.to_end().name())?;
+                }
+                // the reader may already be past the opening '<'
+                let start = match xml[position..].starts_with('<') {
+                    true => position,
+                    false => xml[..position].rfind('<').unwrap_or(position),
+                };
+                let raw = xml[start..reader.buffer_position()].trim().to_owned();
+                match (parent, name.as_str()) {
+                    ("person", name) if PERSON_CHILDREN.contains(&name) => {}
+                    ("profile", name) if PROFILE_CHILDREN.contains(&name) => {}
+                    ("stats", "monitor") => {
+                        if !is_modelled_monitor(&raw)? {
+                            account.stats.children.push(raw);
+                            account.raw_monitors.push(monitor_position);
+                        }
+                        monitor_position += 1;
+                    }
+                    ("person", _) => account.person.children.push(raw),
+                    ("profile", _) => account.profile.children.push(raw),
+                    _ => account.stats.children.push(raw),
+                }
+                continue;
+            }
+            _ => {
+                if !is_empty {
+                    path.push(name);
+                }
+                continue;
+            }
+        };
+        extras.attributes = unknown_attributes(&element, known)?;
+        if !is_empty {
+            path.push(name);
+        }
+    }
+    Ok(accounts)
+}
+
+// puts the extras back onto the person, profile and stats elements of the xml serialized from the account
+pub fn write_extras(xml: &str, extras: &AccountExtras) -> Result<String, ProfileServerError> {
+    if extras.is_empty() {
+        return Ok(xml.to_owned());
+    }
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Start(mut element) => {
+                if let Some(element_extras) = extras.element(element.name().as_ref()) {
+                    element.extend_attributes(element_extras.attributes.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+                }
+                writer.write_event(Event::Start(element))?;
+            }
+            Event::Empty(mut element) => match extras.element(element.name().as_ref()) {
+                Some(element_extras) => {
+                    element.extend_attributes(element_extras.attributes.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+                    if element_extras.children.is_empty() {
+                        writer.write_event(Event::Empty(element))?;
+                        continue;
+                    }
+                    let end = element.to_end().into_owned();
+                    writer.write_event(Event::Start(element))?;
+                    for child in element_extras.children.iter() {
+                        writer.write_event(Event::Text(BytesText::from_escaped(child.as_str())))?;
+                    }
+                    writer.write_event(Event::End(end))?;
+                }
+                None => writer.write_event(Event::Empty(element))?,
+            },
+            Event::End(element) => {
+                if let Some(element_extras) = extras.element(element.name().as_ref()) {
+                    for child in element_extras.children.iter() {
+                        writer.write_event(Event::Text(BytesText::from_escaped(child.as_str())))?;
+                    }
+                }
+                writer.write_event(Event::End(element))?;
+            }
+            event => writer.write_event(event)?,
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
@@ -1,16 +1,19 @@
-use std::net::SocketAddr;
 
-use axum::extract::{ConnectInfo, State};
+use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum_macros::debug_handler;
 
+use super::super::client_addr::ClientAddr;
 use super::super::state::AppState;
+use super::super::tracing::record_player;
+use super::bans::{check_ip_bans, check_player_bans};
 use super::errors::ProfileServerError;
+use super::rate_limit::{check_enlistment_rate_limit, check_rate_limits};
 use super::util::HEADERS;
 use super::util::{
-    check_ip_allowlist, check_realm_is_configured, check_sid, enlist_player, get_account,
-    get_player, get_realm, make_account_xml, make_init_profile_xml,
+    check_ip_allowlist, check_realm_has_capacity, check_realm_is_configured, check_sid,
+    enlist_player, get_account, get_player, get_realm, make_account_xml, make_init_profile_xml,
 };
 use super::validation::ValidatedQuery;
 
@@ -18,20 +21,28 @@ use super::params::GetProfileParams;
 
 #[debug_handler]
 pub async fn rwr1_get_profile_handler(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientAddr(client_ip): ClientAddr,
     State(state): State<AppState>,
     ValidatedQuery(params): ValidatedQuery<GetProfileParams>,
 ) -> Result<Response, ProfileServerError> {
-    // check that the client addr is an allowed ip
-    check_ip_allowlist(&state, addr.ip())?;
+    // tag the request span so that everything logged for this request can be found by realm and player
+    tracing::Span::current().record("realm", params.realm.as_str());
+    record_player(&params.username, params.hash, params.sid);
     // check that the realm has been configured, see fn comments for more detail
-    check_realm_is_configured(&state, &params.realm)?;
+    let realm_config = check_realm_is_configured(&state, &params.realm)?;
+    // check that the client addr is an allowed ip for this realm
+    check_ip_allowlist(&state, &realm_config, client_ip)?;
+    // check that neither the client nor the realm is making too many requests
+    check_rate_limits(&state, &realm_config, client_ip)?;
     // check if the sid is allowed|blocked
-    check_sid(&state, params.sid)?;
+    check_sid(&state, &realm_config, params.sid)?;
+    // check the client, the player and their sid against the bans in force
+    check_ip_bans(&state, &realm_config, client_ip).await?;
+    check_player_bans(&state, &realm_config, params.hash, params.sid).await?;
 
     // get the realm, making it if it doesn't exist yet
     tracing::info!("locating realm '{}'...", &params.realm);
-    let realm_lock = get_realm(&state, &params.realm, &params.realm_digest).await?;
+    let realm_lock = get_realm(&state, &realm_config, &params.realm_digest, client_ip).await?;
     let realm = realm_lock.read().await;
 
     // find the player, if any
@@ -53,8 +64,12 @@ pub async fn rwr1_get_profile_handler(
                 "player '{}' not registered, attempting to enlist them...",
                 &params.username
             );
+            // a new player will need an account in this realm, so make sure there is room for one
+            check_realm_has_capacity(&state, &realm_config, realm.id).await?;
+            check_enlistment_rate_limit(&state, &realm_config)?;
             // enlist player and get back player model
             let player = enlist_player(&state, &params).await?;
+            state.metrics.observe_player(&realm.name, player.hash);
             // make an initialisation profile for the player
             let init_profile_xml = make_init_profile_xml(&player.username, &player.rid)?;
             tracing::info!(
@@ -68,6 +83,7 @@ pub async fn rwr1_get_profile_handler(
         }
         Some(player) => {
             tracing::info!("found papers for player '{}'", &player.username);
+            state.metrics.observe_player(&realm.name, player.hash);
             // we have a player, try to retrieve an account for this player
             let opt_account = get_account(&state, &realm, &player).await?;
             match opt_account {
@@ -79,6 +95,7 @@ pub async fn rwr1_get_profile_handler(
                         player.username,
                         realm.name
                     );
+                    check_realm_has_capacity(&state, &realm_config, realm.id).await?;
                     // resend another init profile here :D
                     let init_profile_xml = make_init_profile_xml(&player.username, &player.rid)?;
                     tracing::info!(
@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+
+use chrono::Utc;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::super::config::AppConfiguration;
+use super::errors::ProfileServerError;
+use super::account_items::load_account_items;
+use super::util::upsert_account;
+use entity::{Account, AccountColumn, AccountModel};
+use entity::{AccountHistory, AccountHistoryActiveModel, AccountHistoryColumn, AccountHistoryModel};
+
+pub const SOURCE_SET_PROFILE: &str = "set_profile";
+pub const SOURCE_BASELINE: &str = "baseline";
+pub const SOURCE_RESTORE: &str = "restore";
+pub const SOURCE_RELEASE: &str = "release";
+
+// the account columns that hold json, expanded when diffing so that changes show up item by item
+const JSON_COLUMNS: [&str; 6] = ["loadout", "backpack", "stash", "kill_combos", "criteria_monitors", "extras"];
+
+#[derive(Debug, Serialize)]
+pub struct AccountChange {
+    pub path: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+fn make_history_model(
+    account: &AccountModel,
+    source: &str,
+    source_ip: Option<IpAddr>,
+) -> Result<AccountHistoryActiveModel, ProfileServerError> {
+    Ok(AccountHistoryActiveModel {
+        realm_id: ActiveValue::Set(account.realm_id),
+        hash: ActiveValue::Set(account.hash),
+        created_at: ActiveValue::Set(Utc::now()),
+        source: ActiveValue::Set(source.to_owned()),
+        source_ip: ActiveValue::Set(source_ip.map(|ip| ip.to_string())),
+        snapshot: ActiveValue::Set(serde_json::to_string(account)?),
+        ..Default::default()
+    })
+}
+
+pub async fn record_account_history(
+    db_conn: &impl ConnectionTrait,
+    realm_id: i32,
+    accounts: &[AccountModel],
+    source: &str,
+    source_ip: Option<IpAddr>,
+    limit: u64,
+) -> Result<(), ProfileServerError> {
+    if limit == 0 || accounts.is_empty() {
+        return Ok(());
+    }
+    let hashes: Vec<i64> = accounts.iter().map(|account| account.hash).collect();
+    // accounts written before history was kept get their current state saved first, so there is always a way back
+    let with_history: BTreeSet<i64> = AccountHistory::find()
+        .select_only()
+        .column(AccountHistoryColumn::Hash)
+        .distinct()
+        .filter(AccountHistoryColumn::RealmId.eq(realm_id))
+        .filter(AccountHistoryColumn::Hash.is_in(hashes.clone()))
+        .into_tuple::<i64>()
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .collect();
+    let without_history: Vec<i64> = hashes
+        .iter()
+        .copied()
+        .filter(|hash| !with_history.contains(hash))
+        .collect();
+    let mut snapshots = Vec::with_capacity(accounts.len());
+    if !without_history.is_empty() {
+        let mut existing_accounts = Account::find()
+            .filter(AccountColumn::RealmId.eq(realm_id))
+            .filter(AccountColumn::Hash.is_in(without_history))
+            .all(db_conn)
+            .await?;
+        load_account_items(db_conn, &mut existing_accounts).await?;
+        for account in existing_accounts.iter() {
+            snapshots.push(make_history_model(account, SOURCE_BASELINE, None)?);
+        }
+    }
+    for account in accounts.iter() {
+        snapshots.push(make_history_model(account, source, source_ip)?);
+    }
+    AccountHistory::insert_many(snapshots).exec(db_conn).await?;
+
+    // only keep the newest `limit` versions of each account
+    for hash in hashes {
+        // sqlite won't take an OFFSET without a LIMIT, so skip the keepers here instead
+        let expired: Vec<i32> = AccountHistory::find()
+            .select_only()
+            .column(AccountHistoryColumn::Id)
+            .filter(AccountHistoryColumn::RealmId.eq(realm_id))
+            .filter(AccountHistoryColumn::Hash.eq(hash))
+            .order_by_desc(AccountHistoryColumn::Id)
+            .into_tuple::<i32>()
+            .all(db_conn)
+            .await?
+            .into_iter()
+            .skip(limit as usize)
+            .collect();
+        if !expired.is_empty() {
+            AccountHistory::delete_many()
+                .filter(AccountHistoryColumn::Id.is_in(expired))
+                .exec(db_conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_account_history(
+    db_conn: &impl ConnectionTrait,
+    realm_id: i32,
+    player_hash: i64,
+) -> Result<Vec<AccountHistoryModel>, ProfileServerError> {
+    Ok(AccountHistory::find()
+        .filter(AccountHistoryColumn::RealmId.eq(realm_id))
+        .filter(AccountHistoryColumn::Hash.eq(player_hash))
+        .order_by_desc(AccountHistoryColumn::Id)
+        .all(db_conn)
+        .await?)
+}
+
+pub async fn get_account_history(
+    db_conn: &impl ConnectionTrait,
+    realm_id: i32,
+    player_hash: i64,
+    history_id: i32,
+) -> Result<Option<AccountHistoryModel>, ProfileServerError> {
+    Ok(AccountHistory::find_by_id(history_id)
+        .filter(AccountHistoryColumn::RealmId.eq(realm_id))
+        .filter(AccountHistoryColumn::Hash.eq(player_hash))
+        .one(db_conn)
+        .await?)
+}
+
+pub fn snapshot_account(history: &AccountHistoryModel) -> Result<AccountModel, ProfileServerError> {
+    Ok(serde_json::from_str(&history.snapshot)?)
+}
+
+pub async fn restore_account_history(
+    db_conn: &DatabaseConnection,
+    history: &AccountHistoryModel,
+    source_ip: Option<IpAddr>,
+    app_config: &AppConfiguration,
+) -> Result<AccountModel, ProfileServerError> {
+    let account = snapshot_account(history)?;
+    let txn = db_conn.begin().await?;
+    // the restore is itself a version, so it can be undone like any other write
+    record_account_history(
+        &txn,
+        account.realm_id,
+        std::slice::from_ref(&account),
+        SOURCE_RESTORE,
+        source_ip,
+        app_config.ps_account_history_limit,
+    )
+    .await?;
+    upsert_account(&txn, &account, app_config.ps_item_storage).await?;
+    txn.commit().await?;
+    Ok(account)
+}
+
+pub fn diff_accounts(from: &AccountModel, to: &AccountModel) -> Result<Vec<AccountChange>, ProfileServerError> {
+    let mut changes = Vec::new();
+    diff_values(String::new(), &expand_account(from)?, &expand_account(to)?, &mut changes);
+    Ok(changes)
+}
+
+fn expand_account(account: &AccountModel) -> Result<Value, ProfileServerError> {
+    let mut json = serde_json::to_value(account)?;
+    for column in JSON_COLUMNS {
+        if let Some(Value::String(blob)) = json.get(column) {
+            // leave anything that doesn't parse as the raw string, it still diffs
+            if let Ok(expanded) = serde_json::from_str::<Value>(blob) {
+                json[column] = expanded;
+            }
+        }
+    }
+    Ok(json)
+}
+
+fn diff_values(path: String, from: &Value, to: &Value, changes: &mut Vec<AccountChange>) {
+    match (from, to) {
+        (Value::Object(from_map), Value::Object(to_map)) => {
+            let keys: BTreeSet<&String> = from_map.keys().chain(to_map.keys()).collect();
+            for key in keys {
+                let child_path = if path.is_empty() { key.to_owned() } else { format!("{path}.{key}") };
+                diff_values(
+                    child_path,
+                    from_map.get(key).unwrap_or(&Value::Null),
+                    to_map.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(from_items), Value::Array(to_items)) => {
+            for index in 0..from_items.len().max(to_items.len()) {
+                diff_values(
+                    format!("{path}[{index}]"),
+                    from_items.get(index).unwrap_or(&Value::Null),
+                    to_items.get(index).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ if from != to => changes.push(AccountChange {
+            path,
+            from: from.clone(),
+            to: to.clone(),
+        }),
+        _ => {}
+    }
+}
@@ -0,0 +1,108 @@
+use super::super::catalog::{ItemCatalog, ItemClass};
+use super::xml::{ItemStoreXml, PlayerXml};
+
+fn is_known(catalog: &ItemCatalog, key: &str, class: Option<ItemClass>) -> bool {
+    // empty loadout slots have no key
+    if key.is_empty() {
+        return true;
+    }
+    match catalog.class_of(key) {
+        Some(catalog_class) => class.is_none_or(|class| class == catalog_class),
+        None => false,
+    }
+}
+
+fn find_unknown_stored_items(catalog: &ItemCatalog, store: &ItemStoreXml, store_name: &str, unknown: &mut Vec<String>) {
+    for item in store.items.iter() {
+        if !is_known(catalog, &item.key, ItemClass::from_store_class(item.class)) {
+            unknown.push(format!("{store_name} '{}' (class {})", item.key, item.class));
+        }
+    }
+}
+
+// describes each item in the save that the catalog doesn't have, or has under a different class
+pub fn find_unknown_items(catalog: &ItemCatalog, player_xml: &PlayerXml) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for item in player_xml.person.equipped_items.iter() {
+        if !is_known(catalog, &item.key, ItemClass::from_slot(item.slot)) {
+            unknown.push(format!("slot {} '{}'", item.slot, item.key));
+        }
+    }
+    find_unknown_stored_items(catalog, &player_xml.person.backpack, "backpack", &mut unknown);
+    find_unknown_stored_items(catalog, &player_xml.person.stash, "stash", &mut unknown);
+    unknown
+}
+
+pub fn drop_unknown_items(catalog: &ItemCatalog, player_xml: &mut PlayerXml) {
+    for item in player_xml.person.equipped_items.iter_mut() {
+        if !is_known(catalog, &item.key, ItemClass::from_slot(item.slot)) {
+            // leave the slot in place but empty, the way the game writes an unused slot
+            item.index = -1;
+            item.amount = 0;
+            item.key.clear();
+        }
+    }
+    for store in [&mut player_xml.person.backpack, &mut player_xml.person.stash] {
+        store
+            .items
+            .retain(|item| is_known(catalog, &item.key, ItemClass::from_store_class(item.class)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing::{fixture_path, vanilla_player_xml};
+
+    fn catalog(path: &str) -> ItemCatalog {
+        ItemCatalog::load(&fixture_path(path)).unwrap()
+    }
+
+    #[test]
+    fn unknown_items_are_found() {
+        // what the log and reject policies go by
+        let player_xml = vanilla_player_xml();
+        assert_eq!(find_unknown_items(&catalog("items"), &player_xml), vec!["stash 'g36.weapon' (class 0)"]);
+        // known items filed under another class are as good as unknown
+        assert_eq!(
+            find_unknown_items(&catalog("items.toml"), &player_xml),
+            vec!["stash 'vest1.carry_item' (class 3)", "stash 'g36.weapon' (class 0)"]
+        );
+
+        let mut player_xml = vanilla_player_xml();
+        player_xml.person.equipped_items[0].key = String::from("rpg7.weapon");
+        player_xml.person.equipped_items[2].slot = 0;
+        assert_eq!(
+            find_unknown_items(&catalog("items"), &player_xml),
+            vec![
+                "slot 0 'rpg7.weapon'",
+                "slot 0 'hand_grenade.projectile'",
+                "stash 'g36.weapon' (class 0)"
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_items_are_dropped() {
+        let catalog = catalog("items");
+        let mut player_xml = vanilla_player_xml();
+        player_xml.person.equipped_items[0].key = String::from("rpg7.weapon");
+        drop_unknown_items(&catalog, &mut player_xml);
+        assert!(find_unknown_items(&catalog, &player_xml).is_empty());
+
+        // the slot stays, emptied
+        let slots: Vec<(u8, i32, u16, &str)> = player_xml
+            .person
+            .equipped_items
+            .iter()
+            .map(|item| (item.slot, item.index, item.amount, item.key.as_str()))
+            .collect();
+        assert_eq!(
+            slots,
+            vec![(0, -1, 0, ""), (1, -1, 0, ""), (2, 1, 2, "hand_grenade.projectile"), (4, 0, 1, "vest2.carry_item")]
+        );
+        let stored = |store: &ItemStoreXml| store.items.iter().map(|item| item.key.to_owned()).collect::<Vec<_>>();
+        assert_eq!(stored(&player_xml.person.backpack), vec!["m16a4.weapon"]);
+        assert_eq!(stored(&player_xml.person.stash), vec!["vest1.carry_item"]);
+    }
+}
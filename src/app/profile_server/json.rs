@@ -1,6 +1,84 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 
+use super::errors::ProfileServerError;
+use super::extras::AccountExtras;
 use super::xml::{EntryXml, EquippedItemXml, MonitorXml, StoredItemXml};
+use entity::AccountModel;
+
+// the shape every blob is written in, tagged as "v" on the blob itself, bump it whenever a blob changes shape
+// and teach that blob's Blob::upgrade how to get from the previous version, see test/fixtures/account_json
+// v0: untagged, and kill_combos is left blank for players that never reported a kill combo monitor
+// v1: tagged with "v"
+pub const SCHEMA_VERSION: u64 = 1;
+
+pub trait Blob: Serialize + DeserializeOwned {
+    fn empty() -> Self;
+
+    // takes the json of a blob written at `version` to `version + 1`
+    fn upgrade(version: u64, value: Value) -> Result<Value, ProfileServerError> {
+        match version {
+            0 if value.is_null() => Ok(serde_json::to_value(Self::empty())?),
+            _ => Ok(value),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Tagged<'a, T> {
+    v: u64,
+    #[serde(flatten)]
+    blob: &'a T,
+}
+
+pub fn to_blob<T: Blob>(blob: &T) -> Result<String, ProfileServerError> {
+    Ok(serde_json::to_string(&Tagged { v: SCHEMA_VERSION, blob })?)
+}
+
+pub fn from_blob<T: Blob>(blob: &str) -> Result<T, ProfileServerError> {
+    Ok(read_blob(blob)?.0)
+}
+
+// reads a blob written at any version up to SCHEMA_VERSION, also giving back the version it was written at
+fn read_blob<T: Blob>(blob: &str) -> Result<(T, u64), ProfileServerError> {
+    let mut value = match blob.trim() {
+        "" => Value::Null,
+        blob => serde_json::from_str(blob)?,
+    };
+    let version = match value.get("v") {
+        Some(v) => v
+            .as_u64()
+            .filter(|v| *v <= SCHEMA_VERSION)
+            .ok_or_else(|| ProfileServerError::JsonSchemaUnsupported(v.to_string()))?,
+        None => 0,
+    };
+    for from_version in version..SCHEMA_VERSION {
+        value = T::upgrade(from_version, value)?;
+    }
+    Ok((serde_json::from_value(value)?, version))
+}
+
+fn upgrade_column<T: Blob>(column: &mut String) -> Result<bool, ProfileServerError> {
+    let (blob, version) = read_blob::<T>(column)?;
+    if version == SCHEMA_VERSION {
+        return Ok(false);
+    }
+    *column = to_blob(&blob)?;
+    Ok(true)
+}
+
+// rewrites any blob of the account written at an older version, returning whether anything changed
+pub fn upgrade_account_blobs(account: &mut AccountModel) -> Result<bool, ProfileServerError> {
+    let upgraded = [
+        upgrade_column::<Loadout>(&mut account.loadout)?,
+        upgrade_column::<ItemStore>(&mut account.backpack)?,
+        upgrade_column::<ItemStore>(&mut account.stash)?,
+        upgrade_column::<KillCombos>(&mut account.kill_combos)?,
+        upgrade_column::<CriteriaMonitors>(&mut account.criteria_monitors)?,
+        upgrade_column::<AccountExtras>(&mut account.extras)?,
+    ];
+    Ok(upgraded.contains(&true))
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Loadout {
@@ -8,6 +86,7 @@ pub struct Loadout {
 }
 
 impl Loadout {
+    #[allow(clippy::redundant_closure)]
     pub fn new(equipped_items: &[EquippedItemXml]) -> Self {
         Self {
             slots: equipped_items
@@ -30,6 +109,12 @@ pub struct EquippedItem {
     pub amount: u16,
 }
 
+impl Blob for Loadout {
+    fn empty() -> Self {
+        Self { slots: Vec::new() }
+    }
+}
+
 impl EquippedItem {
     pub fn new(item: &EquippedItemXml) -> Self {
         Self {
@@ -61,6 +146,12 @@ impl ItemStore {
     }
 }
 
+impl Blob for ItemStore {
+    fn empty() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct StoredItem {
     #[serde(rename = "c")]
@@ -91,11 +182,23 @@ impl KillCombos {
     }
 }
 
+impl Blob for KillCombos {
+    fn empty() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CriteriaMonitors {
     pub monitors: Vec<CriteriaMonitor>,
 }
 
+impl Blob for CriteriaMonitors {
+    fn empty() -> Self {
+        Self { monitors: Vec::new() }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CriteriaMonitor {
     #[serde(rename = "n")]
@@ -115,3 +218,56 @@ impl CriteriaMonitor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::app::testing::account_model;
+
+    const JSON_COLUMNS: [&str; 6] = ["loadout", "backpack", "stash", "kill_combos", "criteria_monitors", "extras"];
+
+    fn json_columns(account: &mut AccountModel) -> [&mut String; 6] {
+        [
+            &mut account.loadout,
+            &mut account.backpack,
+            &mut account.stash,
+            &mut account.kill_combos,
+            &mut account.criteria_monitors,
+            &mut account.extras,
+        ]
+    }
+
+    // each fixture holds the json columns as some version of marshalrwr stored them and as they must be upgraded to
+    #[test]
+    fn stored_blobs_upgrade_to_the_current_schema() {
+        let fixtures_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test/fixtures/account_json");
+        let mut fixtures: Vec<PathBuf> = std::fs::read_dir(fixtures_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect();
+        fixtures.sort();
+        assert!(!fixtures.is_empty());
+        for fixture_path in fixtures {
+            let fixture: Value = serde_json::from_str(&std::fs::read_to_string(&fixture_path).unwrap()).unwrap();
+            let name = fixture_path.file_name().unwrap().to_string_lossy();
+            let mut account = account_model(1, 1);
+            for (column, value) in JSON_COLUMNS.iter().zip(json_columns(&mut account)) {
+                *value = fixture["stored"][column].as_str().unwrap().to_owned();
+            }
+
+            upgrade_account_blobs(&mut account).unwrap();
+            for (column, value) in JSON_COLUMNS.iter().zip(json_columns(&mut account)) {
+                assert_eq!(value.as_str(), fixture["upgraded"][column].as_str().unwrap(), "{name} {column}");
+            }
+            // a second pass must find nothing left to upgrade
+            let upgraded = account.clone();
+            assert!(!upgrade_account_blobs(&mut account).unwrap(), "{name}");
+            assert_eq!(account, upgraded, "{name}");
+        }
+    }
+}
@@ -1,8 +1,15 @@
+pub mod account_items;
+pub mod anomaly;
+pub mod bans;
 pub(super) mod errors;
+pub(super) mod extras;
 pub mod get;
+pub mod history;
+pub(super) mod items;
 pub(super) mod json;
 pub(super) mod params;
+pub mod rate_limit;
 pub mod set;
-pub(super) mod util;
+pub mod util;
 pub(super) mod validation;
 pub(super) mod xml;
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::super::config::{RateLimit, RealmConfiguration};
+use super::super::state::AppState;
+use super::errors::ProfileServerError;
+
+// at most this many buckets are kept per kind, a new key beyond that is turned away until some are pruned
+const MAX_BUCKETS: usize = 4096;
+// how often the buckets that have filled back up (and so are no different from new ones) are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+    // worked out from the limit the bucket was last taken from, which differs by realm and across config reloads
+    full_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate_limit: &RateLimit, now: Instant) -> Self {
+        TokenBucket { tokens: f64::from(rate_limit.requests), updated: now, full_at: now }
+    }
+
+    fn per_sec(rate_limit: &RateLimit) -> f64 {
+        f64::from(rate_limit.requests) / rate_limit.per_secs as f64
+    }
+
+    fn refill(&mut self, rate_limit: &RateLimit, now: Instant) {
+        let capacity = f64::from(rate_limit.requests);
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * Self::per_sec(rate_limit)).min(capacity);
+        self.updated = now;
+    }
+
+    fn take(&mut self, rate_limit: &RateLimit, now: Instant) -> bool {
+        self.refill(rate_limit, now);
+        let taken = self.tokens >= 1.0;
+        if taken {
+            self.tokens -= 1.0;
+        }
+        let missing = f64::from(rate_limit.requests) - self.tokens;
+        self.full_at = now + Duration::from_secs_f64(missing / Self::per_sec(rate_limit));
+        taken
+    }
+}
+
+struct BucketMap<K> {
+    buckets: HashMap<K, TokenBucket>,
+    pruned: Instant,
+}
+
+struct Buckets<K>(Mutex<BucketMap<K>>);
+
+impl<K: Eq + Hash> Default for Buckets<K> {
+    fn default() -> Self {
+        Buckets(Mutex::new(BucketMap { buckets: HashMap::new(), pruned: Instant::now() }))
+    }
+}
+
+impl<K: Eq + Hash> Buckets<K> {
+    // takes a token from the key's bucket, false if there wasn't one to take
+    fn take(&self, key: K, rate_limit: &RateLimit) -> bool {
+        self.take_at(key, rate_limit, Instant::now())
+    }
+
+    fn take_at(&self, key: K, rate_limit: &RateLimit, now: Instant) -> bool {
+        let mut map = self.0.lock().unwrap();
+        if now.saturating_duration_since(map.pruned) >= PRUNE_INTERVAL {
+            map.buckets.retain(|_, bucket| bucket.full_at > now);
+            map.pruned = now;
+        }
+        if map.buckets.len() >= MAX_BUCKETS && !map.buckets.contains_key(&key) {
+            tracing::warn!("{MAX_BUCKETS} rate limit buckets are in use, turning away a new one until some fill back up");
+            return false;
+        }
+        map.buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::new(rate_limit, now))
+            .take(rate_limit, now)
+    }
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    ips: Buckets<IpAddr>,
+    realms: Buckets<String>,
+    enlistments: Buckets<String>,
+}
+
+pub fn check_rate_limits(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    ip: IpAddr,
+) -> Result<(), ProfileServerError> {
+    let app_config = state.config();
+    if let Some(rate_limit) = &app_config.ps_ip_rate_limit {
+        if !state.rate_limiter.ips.take(ip, rate_limit) {
+            tracing::warn!("client address '{ip}' is over ps_ip_rate_limit");
+            return Err(ProfileServerError::RateLimited(format!("ip address '{ip}'")));
+        }
+    }
+    if let Some(rate_limit) = app_config.realm_rate_limit(realm_config) {
+        if !state.rate_limiter.realms.take(realm_config.name.to_owned(), &rate_limit) {
+            tracing::warn!("realm '{}' is over its rate limit", realm_config.name);
+            return Err(ProfileServerError::RateLimited(format!("realm '{}'", realm_config.name)));
+        }
+    }
+    Ok(())
+}
+
+pub fn check_enlistment_rate_limit(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+) -> Result<(), ProfileServerError> {
+    // every enlistment is a new player row, so these are held to a stricter limit of their own
+    if let Some(rate_limit) = state.config().enlist_rate_limit(realm_config) {
+        if !state.rate_limiter.enlistments.take(realm_config.name.to_owned(), &rate_limit) {
+            tracing::warn!("realm '{}' is over its enlistment rate limit", realm_config.name);
+            return Err(ProfileServerError::EnlistmentRateLimited(realm_config.name.to_owned()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: RateLimit = RateLimit { requests: 1, per_secs: 1 };
+    const SLOW: RateLimit = RateLimit { requests: 1, per_secs: 3600 };
+
+    #[test]
+    fn buckets_are_pruned_by_their_own_limit() {
+        let buckets = Buckets::default();
+        let start = Instant::now();
+        assert!(buckets.take_at("FAST", &FAST, start));
+        assert!(buckets.take_at("SLOW", &SLOW, start));
+        assert!(!buckets.take_at("SLOW", &SLOW, start));
+
+        // a prune triggered by the fast realm must not mistake the slow realm's bucket for a full one
+        let later = start + PRUNE_INTERVAL;
+        assert!(buckets.take_at("FAST", &FAST, later));
+        assert_eq!(buckets.0.lock().unwrap().buckets.len(), 2);
+        assert!(!buckets.take_at("SLOW", &SLOW, later));
+        let later = later + PRUNE_INTERVAL;
+        assert!(!buckets.take_at("SLOW", &SLOW, later));
+        assert_eq!(buckets.0.lock().unwrap().buckets.len(), 1);
+    }
+
+    #[test]
+    fn buckets_are_capped() {
+        let buckets = Buckets::default();
+        let start = Instant::now();
+        for key in 0..MAX_BUCKETS {
+            assert!(buckets.take_at(key, &SLOW, start));
+        }
+        assert!(!buckets.take_at(MAX_BUCKETS, &FAST, start));
+        // there is room again once the buckets have filled back up and been pruned
+        let later = start + Duration::from_secs(SLOW.per_secs);
+        assert!(buckets.take_at(MAX_BUCKETS, &FAST, later));
+        assert_eq!(buckets.0.lock().unwrap().buckets.len(), 1);
+    }
+}
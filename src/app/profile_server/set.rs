@@ -1,48 +1,82 @@
-use std::net::SocketAddr;
 use std::sync::Arc;
 
-use axum::extract::{ConnectInfo, State};
+use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum_macros::debug_handler;
 use migration::OnConflict;
-use sea_orm::EntityTrait;
+use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, TransactionTrait};
 
+use super::super::client_addr::ClientAddr;
+use super::super::config::{AnomalyAction, ItemPolicy};
 use super::super::state::AppState;
+use super::super::tracing::record_player;
+use super::account_items::store_account_items;
+use super::anomaly::{check_account, describe_hits, most_severe, record_anomaly, AnomalyHit};
+use super::bans::{check_ip_bans, check_player_bans};
 use super::errors::ProfileServerError;
+use super::history::{record_account_history, SOURCE_SET_PROFILE};
+use super::items::{drop_unknown_items, find_unknown_items};
+use super::rate_limit::check_rate_limits;
 use super::validation::{ValidatedQuery, ValidatedXmlBody};
 use super::xml::SetProfileDataXml;
 
 use super::params::SetProfileParams;
 use super::util::{
-    check_ip_allowlist, check_realm_is_configured, get_player, get_realm, make_account_model,
+    check_ip_allowlist, check_player_xml_identity, check_realm_is_configured, check_realm_is_writable,
+    count_new_realm_accounts, get_account_from_db, get_player, get_realm, make_account_model,
 };
 use super::util::{ACCOUNT_COLUMNS, HEADERS};
 
-use entity::{Account, AccountActiveModel, AccountColumn, AccountModel};
+use entity::{Account, AccountColumn, AccountModel};
 
 #[debug_handler]
 pub async fn rwr1_set_profile_handler(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientAddr(client_ip): ClientAddr,
     State(state): State<AppState>,
     ValidatedQuery(params): ValidatedQuery<SetProfileParams>,
-    ValidatedXmlBody(data): ValidatedXmlBody<SetProfileDataXml>,
+    ValidatedXmlBody(mut data): ValidatedXmlBody<SetProfileDataXml>,
 ) -> Result<Response, ProfileServerError> {
-    // check that the client addr is an allowed ip
-    check_ip_allowlist(&state, addr.ip())?;
-
+    // tag the request span so that everything logged for this request can be found by realm
+    tracing::Span::current().record("realm", params.realm.as_str());
     // check that the realm has been configured, see fn comments for more detail
-    check_realm_is_configured(&state, &params.realm)?;
+    let realm_config = check_realm_is_configured(&state, &params.realm)?;
+
+    // check that the client addr is an allowed ip for this realm
+    check_ip_allowlist(&state, &realm_config, client_ip)?;
+
+    // check that neither the client nor the realm is making too many requests
+    check_rate_limits(&state, &realm_config, client_ip)?;
+
+    // a banned client can't save anything at all
+    check_ip_bans(&state, &realm_config, client_ip).await?;
+
+    // read-only realms serve profiles but never store them
+    check_realm_is_writable(&realm_config)?;
 
     // get the realm, making it if it doesn't exist yet
     tracing::info!("locating realm '{}'...", &params.realm);
-    let realm_lock = get_realm(&state, &params.realm, &params.realm_digest).await?;
+    let realm_lock = get_realm(&state, &realm_config, &params.realm_digest, client_ip).await?;
     let realm = realm_lock.write().await;
 
     // tracing::debug!("{data:#?}");
-    let mut accounts_to_update: Vec<AccountActiveModel> = Vec::new();
-    for player_xml in data.players.iter() {
+    let app_config = state.config();
+    let anomaly_rules = app_config.anomaly_rules(&realm_config);
+    let item_policy = app_config.item_policy(&realm_config);
+    let mut account_models: Vec<AccountModel> = Vec::new();
+    let mut anomalies: Vec<(AccountModel, Vec<AnomalyHit>)> = Vec::new();
+    // accounts this set xml adds to the realm, rather than updates, for the realm's cached account count
+    let mut new_accounts: u64 = 0;
+    for player_xml in data.players.iter_mut() {
+        record_player(&player_xml.profile.username, player_xml.hash, player_xml.profile.sid);
         tracing::info!("processing set xml for player '{}'...", player_xml.hash);
+        // make sure the player xml is consistent with itself before trusting any of it
+        check_player_xml_identity(player_xml)?;
+        // a banned player's save is left out, the rest of the set xml still goes through
+        if let Err(err) = check_player_bans(&state, &realm_config, player_xml.hash, player_xml.profile.sid).await {
+            tracing::warn!("{err}, leaving their save out");
+            continue;
+        }
         // get the player from cache/db, remembering that get_player does all the account sid/rid verification
         // by itself if it encounters an existing player in the cache or db
         let opt_player = get_player(
@@ -64,48 +98,119 @@ pub async fn rwr1_set_profile_handler(
                 ));
             }
             Some(player) => {
+                state.metrics.observe_player(&realm.name, player.hash);
                 tracing::info!(
                     "creating account model for player '{}' from xml...",
                     &player.username
                 );
-                // construct account active model from player xml
-                let account = make_account_model(realm.id, player_xml)?;
+                // check the items in the save against the item catalog, if we have one
+                if let Some(item_catalog) = state.item_catalog.as_deref() {
+                    let unknown_items = match item_policy {
+                        ItemPolicy::Off => Vec::new(),
+                        _ => find_unknown_items(item_catalog, player_xml),
+                    };
+                    if !unknown_items.is_empty() {
+                        tracing::warn!(
+                            "save for account ('{}','{}') has items not in the catalog, {}: {}",
+                            realm.name,
+                            player.username,
+                            item_policy.as_str(),
+                            unknown_items.join(", ")
+                        );
+                        match item_policy {
+                            ItemPolicy::Drop => drop_unknown_items(item_catalog, player_xml),
+                            // a rejected save is left out, the rest of the set xml still goes through
+                            ItemPolicy::Reject => continue,
+                            ItemPolicy::Off | ItemPolicy::Log => {}
+                        }
+                    }
+                }
+                // construct the account model from player xml
+                let account_model: AccountModel = make_account_model(realm.id, player_xml)?.try_into()?;
+                // compare the save with the account we already hold before accepting it
+                let cached_account = state.cache.accounts.get(&(realm.id, player.hash));
+                state.metrics.observe_cache_lookup("accounts", cached_account.is_some());
+                let stored_account = match cached_account {
+                    Some(stored_account) => Some(stored_account),
+                    None => get_account_from_db(&state.db, realm.id, player.hash).await?.map(Arc::new),
+                };
+                let hits = match &stored_account {
+                    Some(stored_account) => check_account(anomaly_rules, stored_account, &account_model),
+                    None => Vec::new(),
+                };
+                let action = most_severe(&hits);
+                if !hits.is_empty() {
+                    tracing::warn!(
+                        "save for account ('{}','{}') tripped anomaly rules, {}: {}",
+                        realm.name,
+                        player.username,
+                        action.as_str(),
+                        describe_hits(&hits)
+                    );
+                    anomalies.push((account_model.clone(), hits));
+                }
+                // quarantined and rejected saves are held back, the rest of the set xml still goes through
+                if action >= AnomalyAction::Quarantine {
+                    continue;
+                }
                 // add account to vec of accounts to update in bulk insert many
-                accounts_to_update.push(account);
+                if stored_account.is_none() {
+                    new_accounts += 1;
+                }
+                account_models.push(account_model);
             }
         }
     }
 
     // update accounts models in cache
     tracing::debug!("inserting/updating accounts in cache...");
-    for account in accounts_to_update.iter() {
-        // this unwrap should be "safe" as the account was just made^
-        let account_model: AccountModel = account.clone().try_into().unwrap();
-        let hash = account_model.hash;
-        let arc_model = Arc::new(account_model);
+    for account_model in account_models.iter() {
+        let arc_model = Arc::new(account_model.clone());
         state
             .cache
             .accounts
-            .insert((realm.id, hash), arc_model)
+            .insert((realm.id, account_model.hash), arc_model)
             .await;
     }
-    // insert many active model accounts with on_conflict to update
+    // insert many active model accounts with on_conflict to update, keeping a version of each in the history
     tracing::info!("inserting account model(s) into db...");
-    let res = Account::insert_many(accounts_to_update)
-        .on_conflict(
-            OnConflict::columns([AccountColumn::RealmId, AccountColumn::Hash])
-                // update ALL columns
-                .update_columns(ACCOUNT_COLUMNS)
-                .to_owned(),
+    let txn = state.db.begin().await?;
+    for (account_model, hits) in anomalies.iter() {
+        record_anomaly(&txn, account_model, hits, Some(client_ip)).await?;
+    }
+    if !account_models.is_empty() {
+        // history goes first so that accounts without any yet get a baseline of their current state
+        record_account_history(
+            &txn,
+            realm.id,
+            &account_models,
+            SOURCE_SET_PROFILE,
+            Some(client_ip),
+            app_config.ps_account_history_limit,
         )
-        .exec(&state.db)
         .await?;
-
-    tracing::info!(
-        "inserted accounts into db, last insert = ({},{})",
-        res.last_insert_id.0,
-        res.last_insert_id.1
-    );
+        // the items go into their json columns or account_item rows, depending on ps_item_storage
+        let stored_models = store_account_items(&txn, &account_models, app_config.ps_item_storage).await?;
+        let accounts_to_update = stored_models
+            .into_iter()
+            .map(|account_model| account_model.into_active_model().reset_all());
+        let res = Account::insert_many(accounts_to_update)
+            .on_conflict(
+                OnConflict::columns([AccountColumn::RealmId, AccountColumn::Hash])
+                    // update ALL columns
+                    .update_columns(ACCOUNT_COLUMNS)
+                    .to_owned(),
+            )
+            .exec(&txn)
+            .await?;
+        tracing::info!(
+            "inserted accounts into db, last insert = ({},{})",
+            res.last_insert_id.0,
+            res.last_insert_id.1
+        );
+    }
+    txn.commit().await?;
+    count_new_realm_accounts(&state, realm.id, new_accounts);
 
     drop(realm);
     // respond to the game server
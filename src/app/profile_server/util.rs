@@ -1,5 +1,6 @@
 use std::io::Cursor;
 use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{RwLock, RwLockReadGuard};
 
@@ -10,23 +11,32 @@ use quick_xml::{
     events::{BytesEnd, BytesStart, Event},
     writer::Writer,
 };
-use sea_orm::{error::DbErr, ColumnTrait, EntityTrait, QueryFilter};
-use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection};
+use sea_orm::{error::DbErr, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DatabaseConnection, IntoActiveModel, TransactionTrait};
+use migration::OnConflict;
+use chrono::{Duration, Utc};
 use serde::Serialize;
 use subtle::ConstantTimeEq;
 
+use super::super::config::{ItemStorage, RealmConfiguration};
+use super::super::hasher::rwr1_hash_username;
+use super::super::ip_rules::find_matching_rule;
 use super::super::state::AppState;
+use super::account_items::{delete_account_items, load_account_items, store_account_items};
 use super::errors::ProfileServerError;
-use super::json::{CriteriaMonitor, CriteriaMonitors, ItemStore, KillCombos, Loadout};
+use super::json::{to_blob, Blob, CriteriaMonitor, CriteriaMonitors, ItemStore, KillCombos, Loadout};
 use super::params::GetProfileParams;
+use super::extras::write_extras;
 use super::xml::{GetProfileDataXml, PlayerXml};
 use entity::{Account, AccountActiveModel, AccountColumn, AccountModel};
+use entity::{AccountAnomaly, AccountAnomalyColumn, AccountHistory, AccountHistoryColumn};
 use entity::{Player, PlayerActiveModel, PlayerModel};
 use entity::{Realm, RealmActiveModel, RealmColumn, RealmModel};
+use entity::RealmDigestAuditActiveModel;
 
 pub const HEADERS: [(HeaderName, &str); 1] = [(header::CONTENT_TYPE, "text/xml")];
 pub const USERNAME_BLOCKED_CHARS: [char; 5] = ['"', '\'', ',', ';', '`'];
-pub const ACCOUNT_COLUMNS: [AccountColumn; 31] = [
+pub const ACCOUNT_COLUMNS: [AccountColumn; 32] = [
     AccountColumn::RealmId,
     AccountColumn::Hash,
     AccountColumn::GameVersion,
@@ -58,38 +68,131 @@ pub const ACCOUNT_COLUMNS: [AccountColumn; 31] = [
     AccountColumn::LongestDeathStreak,
     AccountColumn::KillCombos,
     AccountColumn::CriteriaMonitors,
+    AccountColumn::Extras,
 ];
 
-pub fn check_ip_allowlist(state: &AppState, ip: IpAddr) -> Result<(), ProfileServerError> {
-    if !state.config.ps_allowed_ips.contains(&ip) {
-        return Err(ProfileServerError::ClientAddressNotAllowed(ip));
+pub fn check_ip_allowlist(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    ip: IpAddr,
+) -> Result<(), ProfileServerError> {
+    let app_config = state.config();
+    let (rules, source) = match &realm_config.allowed_ips {
+        Some(rules) => (rules, format!("realms.{}.allowed_ips", realm_config.name)),
+        None => (&app_config.ps_allowed_ips, String::from("ps_allowed_ips")),
+    };
+    match find_matching_rule(rules, ip, &state.resolver) {
+        Some(rule) => {
+            tracing::info!("client address '{ip}' allowed by rule '{rule}' in {source}");
+            Ok(())
+        }
+        None => {
+            tracing::warn!("client address '{ip}' matched no rule in {source}");
+            Err(ProfileServerError::ClientAddressNotAllowed(ip))
+        }
     }
-    Ok(())
 }
 
-pub fn check_realm_is_configured(state: &AppState, realm: &str) -> Result<(), ProfileServerError> {
-    // check that this realm is in state.config, this acts as a guard whilst the realm digest algo remains a mystery
+pub fn check_realm_is_configured(
+    state: &AppState,
+    realm: &str,
+) -> Result<RealmConfiguration, ProfileServerError> {
+    // check that this realm is in the live config, this acts as a guard whilst the realm digest algo remains a mystery
     // as we cannot derive the digest from knowing the realm secret and pw, the server expects the realms to be named (e.g. ["INCURSION"]) in the config instead
     // when the first request for a realm is received, it will be created in the db with the digest supplied in the first request
     // this should be fine when the IP allowlist for the profile server endpoints is implemented
-    if !state.config.ps_realms.contains(realm) {
-        return Err(ProfileServerError::RealmNotConfigured(String::from(realm)));
+    state
+        .config()
+        .realm(realm)
+        .ok_or_else(|| ProfileServerError::RealmNotConfigured(String::from(realm)))
+}
+
+pub fn check_sid(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    sid: i64,
+) -> Result<(), ProfileServerError> {
+    let app_config = state.config();
+    let allowed_sids = realm_config
+        .allowed_sids
+        .as_ref()
+        .unwrap_or(&app_config.ps_allowed_sids);
+    let blocked_sids = realm_config
+        .blocked_sids
+        .as_ref()
+        .unwrap_or(&app_config.ps_blocked_sids);
+    if !allowed_sids.is_empty() && !allowed_sids.contains(&sid) {
+        return Err(ProfileServerError::SidNotAllowed(sid));
+    }
+    if blocked_sids.contains(&sid) {
+        return Err(ProfileServerError::SidBlocked(sid));
     }
     Ok(())
 }
 
-pub fn check_sid(state: &AppState, sid: i64) -> Result<(), ProfileServerError> {
-    if !state.config.ps_allowed_sids.is_empty() {
-        if !state.config.ps_allowed_sids.contains(&sid) {
-            return Err(ProfileServerError::SidNotAllowed(sid));
-        }
+pub fn check_player_xml_identity(player_xml: &PlayerXml) -> Result<(), ProfileServerError> {
+    // the same hash/username check the get path makes on its params
+    let username_hash = rwr1_hash_username(&player_xml.profile.username);
+    if player_xml.hash != username_hash {
+        return Err(ProfileServerError::PlayerHashMismatch(
+            player_xml.hash,
+            player_xml.profile.username.to_owned(),
+            username_hash,
+        ));
     }
-    if state.config.ps_blocked_sids.contains(&sid) {
-        return Err(ProfileServerError::SidBlocked(sid));
+    // the rid appears on both the player and profile elements, they must agree
+    if player_xml.rid != player_xml.profile.rid {
+        return Err(ProfileServerError::PlayerRidMismatch(
+            player_xml.hash,
+            player_xml.profile.username.to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_realm_is_writable(realm_config: &RealmConfiguration) -> Result<(), ProfileServerError> {
+    if realm_config.read_only {
+        return Err(ProfileServerError::RealmReadOnly(realm_config.name.to_owned()));
+    }
+    Ok(())
+}
+
+pub async fn check_realm_has_capacity(
+    state: &AppState,
+    realm_config: &RealmConfiguration,
+    realm_id: i32,
+) -> Result<(), ProfileServerError> {
+    if let Some(max_players) = realm_config.max_players {
+        let players = count_realm_accounts(state, realm_id).await?;
+        if players >= max_players {
+            return Err(ProfileServerError::RealmFull(realm_config.name.to_owned(), max_players));
+        }
     }
     Ok(())
 }
 
+async fn count_realm_accounts(state: &AppState, realm_id: i32) -> Result<u64, ProfileServerError> {
+    // only count the accounts when the realm isn't in the cache, set_profile keeps the cached count up to date
+    let cached = state.cache.realm_accounts.get(&realm_id);
+    state.metrics.observe_cache_lookup("realm_accounts", cached.is_some());
+    if let Some(count) = cached {
+        return Ok(count.load(Ordering::Relaxed));
+    }
+    let count = Account::find()
+        .filter(AccountColumn::RealmId.eq(realm_id))
+        .count(&state.db)
+        .await?;
+    state.cache.realm_accounts.insert(realm_id, Arc::new(AtomicU64::new(count))).await;
+    Ok(count)
+}
+
+pub fn count_new_realm_accounts(state: &AppState, realm_id: i32, new_accounts: u64) {
+    // a realm that isn't in the cache will be counted afresh when it's next needed
+    if let Some(count) = state.cache.realm_accounts.get(&realm_id) {
+        count.fetch_add(new_accounts, Ordering::Relaxed);
+    }
+}
+
 pub fn digest_ok(given_digest: &str, valid_digest: &str) -> bool {
     // check the realm digest in constant time mit subtle crate
     // todo: validate that this actually works in constant time XD
@@ -102,16 +205,36 @@ pub fn verify_realm_digest(
     realm_name: &str,
     realm_digest: &str,
     valid_digest: &str,
+    previous_digest: Option<&str>,
 ) -> Result<(), ProfileServerError> {
-    if !digest_ok(realm_digest, valid_digest) {
+    // check both digests regardless, so that which one matched can't be told apart by timing
+    let current_ok = digest_ok(realm_digest, valid_digest);
+    let previous_ok =
+        previous_digest.is_some_and(|previous_digest| digest_ok(realm_digest, previous_digest));
+    if !current_ok && !previous_ok {
         return Err(ProfileServerError::RealmDigestIncorrect(
             String::from(realm_name),
             String::from(realm_digest),
         ));
     }
+    if !current_ok {
+        tracing::warn!("realm '{realm_name}' presented its previous digest, accepted during the rotation grace window");
+    }
     Ok(())
 }
 
+fn previous_digest_expired(realm: &RealmModel) -> bool {
+    realm.previous_digest.is_some()
+        && realm.previous_digest_expires_at.is_none_or(|expires_at| expires_at <= Utc::now())
+}
+
+fn previous_digest_in_grace(realm: &RealmModel) -> Option<&str> {
+    match (&realm.previous_digest, realm.previous_digest_expires_at) {
+        (Some(previous_digest), Some(expires_at)) if expires_at > Utc::now() => Some(previous_digest),
+        _ => None,
+    }
+}
+
 pub fn verify_player_sid_and_rid(
     hash: i64,
     username: &str,
@@ -140,8 +263,34 @@ pub fn verify_player_sid_and_rid(
     Ok(())
 }
 
+fn verify_stored_realm_digest(
+    realm_config: &RealmConfiguration,
+    realm_digest: &str,
+    realm: &RealmModel,
+) -> Result<(), ProfileServerError> {
+    match &realm_config.digest {
+        // the request digest has already been verified against the pinned digest
+        Some(pinned_digest) => {
+            if !digest_ok(pinned_digest, &realm.digest) {
+                tracing::warn!(
+                    "realm '{}' [{}] stored digest differs from the pinned digest, using the pinned digest",
+                    realm.name,
+                    realm.id
+                );
+            }
+            Ok(())
+        }
+        None => verify_realm_digest(
+            &realm.name,
+            realm_digest,
+            &realm.digest,
+            previous_digest_in_grace(realm),
+        ),
+    }
+}
+
 pub async fn get_realm_from_db(
-    db_conn: &DatabaseConnection,
+    db_conn: &impl ConnectionTrait,
     realm_name: &str,
 ) -> Result<Option<RealmModel>, DbErr> {
     let realm = Realm::find()
@@ -151,20 +300,157 @@ pub async fn get_realm_from_db(
     Ok(realm)
 }
 
+pub async fn create_realm(
+    db_conn: &DatabaseConnection,
+    realm_name: &str,
+    realm_digest: &str,
+    created_by: &str,
+) -> Result<RealmModel, DbErr> {
+    let new_realm = RealmActiveModel {
+        name: ActiveValue::Set(realm_name.to_owned()),
+        digest: ActiveValue::Set(realm_digest.to_owned()),
+        created_by: ActiveValue::Set(Some(created_by.to_owned())),
+        created_at: ActiveValue::Set(Some(Utc::now())),
+        ..Default::default()
+    };
+    new_realm.insert(db_conn).await
+}
+
+async fn record_digest_event(
+    db_conn: &impl ConnectionTrait,
+    realm: &RealmModel,
+    event: &str,
+    old_digest: Option<&str>,
+    new_digest: Option<&str>,
+    actor: &str,
+) -> Result<(), DbErr> {
+    let audit = RealmDigestAuditActiveModel {
+        realm_id: ActiveValue::Set(realm.id),
+        event: ActiveValue::Set(event.to_owned()),
+        old_digest: ActiveValue::Set(old_digest.map(String::from)),
+        new_digest: ActiveValue::Set(new_digest.map(String::from)),
+        actor: ActiveValue::Set(Some(actor.to_owned())),
+        created_at: ActiveValue::Set(Utc::now()),
+        ..Default::default()
+    };
+    audit.insert(db_conn).await?;
+    Ok(())
+}
+
+pub async fn rotate_realm_digest(
+    db_conn: &DatabaseConnection,
+    realm: RealmModel,
+    new_digest: &str,
+    grace: Duration,
+    actor: &str,
+) -> Result<RealmModel, DbErr> {
+    // the outgoing digest stays valid until the grace window closes, a zero grace retires it at once
+    let old_digest = realm.digest.clone();
+    let (previous_digest, expires_at) = match grace > Duration::zero() {
+        true => {
+            let Some(expires_at) = Utc::now().checked_add_signed(grace) else {
+                return Err(DbErr::Custom(format!("a grace period of {grace} is out of range")));
+            };
+            (Some(old_digest.clone()), Some(expires_at))
+        }
+        false => (None, None),
+    };
+    let txn = db_conn.begin().await?;
+    let mut rotated_realm: RealmActiveModel = realm.into();
+    rotated_realm.digest = ActiveValue::Set(new_digest.to_owned());
+    rotated_realm.previous_digest = ActiveValue::Set(previous_digest);
+    rotated_realm.previous_digest_expires_at = ActiveValue::Set(expires_at);
+    let realm = rotated_realm.update(&txn).await?;
+    record_digest_event(&txn, &realm, "rotated", Some(&old_digest), Some(new_digest), actor).await?;
+    if expires_at.is_none() {
+        record_digest_event(&txn, &realm, "retired", Some(&old_digest), None, actor).await?;
+    }
+    txn.commit().await?;
+    Ok(realm)
+}
+
+pub async fn retire_previous_realm_digest(
+    db_conn: &DatabaseConnection,
+    realm: RealmModel,
+    actor: &str,
+) -> Result<RealmModel, DbErr> {
+    let Some(previous_digest) = realm.previous_digest.clone() else {
+        return Ok(realm);
+    };
+    let txn = db_conn.begin().await?;
+    let mut retired_realm: RealmActiveModel = realm.into();
+    retired_realm.previous_digest = ActiveValue::Set(None);
+    retired_realm.previous_digest_expires_at = ActiveValue::Set(None);
+    let realm = retired_realm.update(&txn).await?;
+    record_digest_event(&txn, &realm, "retired", Some(&previous_digest), None, actor).await?;
+    txn.commit().await?;
+    Ok(realm)
+}
+
+async fn refresh_cached_realm(
+    state: &AppState,
+    realm_lock: &Arc<RwLock<RealmModel>>,
+) -> Result<(), ProfileServerError> {
+    let mut realm = realm_lock.write().await;
+    // the digest may have been rotated by the cli since this realm was cached
+    if let Some(db_realm) = get_realm_from_db(&state.db, &realm.name).await? {
+        if db_realm != *realm {
+            tracing::info!("reloaded realm '{}' [{}] from db", db_realm.name, db_realm.id);
+            *realm = db_realm;
+        }
+    }
+    // once the grace window has closed the previous digest is retired for good
+    if previous_digest_expired(&realm) {
+        *realm = retire_previous_realm_digest(&state.db, realm.clone(), "grace window").await?;
+        tracing::info!("retired the previous digest of realm '{}' [{}]", realm.name, realm.id);
+    }
+    Ok(())
+}
+
+pub async fn seed_configured_realms(state: &AppState) -> Result<(), DbErr> {
+    // create any realm with a pinned digest up front, so it never has to be trusted on first use
+    for (realm_name, realm_config) in state.config().realms.iter() {
+        let Some(digest) = &realm_config.digest else {
+            continue;
+        };
+        if get_realm_from_db(&state.db, realm_name).await?.is_none() {
+            let realm = create_realm(&state.db, realm_name, digest, "config").await?;
+            tracing::info!("seeded realm '{realm_name}' [{}] from config", realm.id);
+        }
+    }
+    Ok(())
+}
+
 pub async fn get_realm(
     state: &AppState,
-    realm_name: &str,
+    realm_config: &RealmConfiguration,
     realm_digest: &str,
+    client_ip: IpAddr,
 ) -> Result<Arc<RwLock<RealmModel>>, ProfileServerError> {
+    let realm_name = realm_config.name.as_str();
+    // a digest pinned in the config is authoritative over whatever the realm was created with
+    if let Some(pinned_digest) = &realm_config.digest {
+        verify_realm_digest(realm_name, realm_digest, pinned_digest, None)?;
+    }
     // search for realm in cache
-    match state.cache.realms.get(realm_name) {
+    let cached = state.cache.realms.get(realm_name);
+    state.metrics.observe_cache_lookup("realms", cached.is_some());
+    match cached {
         Some(realm_lock) => {
             let _realm_lock = realm_lock.clone();
             let realm = _realm_lock.read().await;
             tracing::debug!("located realm '{realm_name}' [{}] in cache", realm.id);
-            // verify the realm digest
-            verify_realm_digest(realm_name, realm_digest, &realm.digest)?;
-            drop(realm);
+            // reload the realm once its refresh has lapsed, never because of the digest a request came with
+            let refresh_due = state.cache.realm_refreshes.get(realm_name).is_none();
+            if refresh_due || previous_digest_expired(&realm) {
+                drop(realm);
+                state.cache.realm_refreshes.insert(String::from(realm_name), ()).await;
+                refresh_cached_realm(state, &realm_lock).await?;
+                let realm = _realm_lock.read().await;
+                verify_stored_realm_digest(realm_config, realm_digest, &realm)?;
+            } else {
+                verify_stored_realm_digest(realm_config, realm_digest, &realm)?;
+            }
             Ok(realm_lock)
         }
         None => {
@@ -182,21 +468,31 @@ pub async fn get_realm(
                         .realms
                         .insert(String::from(realm_name), arc_model.clone())
                         .await;
+                    state.cache.realm_refreshes.insert(String::from(realm_name), ()).await;
+                    if previous_digest_expired(&realm) {
+                        refresh_cached_realm(state, &arc_model).await?;
+                    }
                     // verify the realm digest
-                    verify_realm_digest(realm_name, realm_digest, &realm.digest)?;
+                    verify_stored_realm_digest(realm_config, realm_digest, &*arc_model.read().await)?;
                     Ok(arc_model)
                 }
                 None => {
+                    // in strict mode a realm is only created if its digest was pinned (and so already verified)
+                    let strict = realm_config.strict.unwrap_or(state.config().ps_strict_realms);
+                    if strict && realm_config.digest.is_none() {
+                        return Err(ProfileServerError::RealmNotSeeded(String::from(realm_name)));
+                    }
                     tracing::debug!("realm '{}' not found in db, creating it...", realm_name);
-                    // create new realm active model
-                    let new_realm = RealmActiveModel {
-                        name: ActiveValue::Set(realm_name.to_owned()),
-                        digest: ActiveValue::Set(realm_digest.to_owned()),
-                        ..Default::default()
-                    };
                     // insert this new realm into the db
-                    let realm = new_realm.insert(&state.db).await?;
-                    tracing::debug!("created realm '{}' [{}] in db", realm_name, realm.id);
+                    let realm =
+                        create_realm(&state.db, realm_name, realm_digest, &client_ip.to_string())
+                            .await?;
+                    tracing::info!(
+                        "created realm '{}' [{}] in db for '{}'",
+                        realm_name,
+                        realm.id,
+                        client_ip
+                    );
                     // insert the model into the realm cache
                     let arc_model = Arc::new(RwLock::new(realm));
                     state
@@ -204,6 +500,7 @@ pub async fn get_realm(
                         .realms
                         .insert(String::from(realm_name), arc_model.clone())
                         .await;
+                    state.cache.realm_refreshes.insert(String::from(realm_name), ()).await;
                     Ok(arc_model)
                 }
             }
@@ -228,7 +525,9 @@ pub async fn get_player(
     rid: &str,
 ) -> Result<Option<Arc<PlayerModel>>, ProfileServerError> {
     // search for player in cache
-    match state.cache.players.get(&player_hash) {
+    let cached = state.cache.players.get(&player_hash);
+    state.metrics.observe_cache_lookup("players", cached.is_some());
+    match cached {
         Some(player) => {
             tracing::debug!("found player '{}' [{}] in cache", username, player_hash);
             // verify the player sid and rid (digest)
@@ -276,15 +575,19 @@ pub async fn get_player(
 }
 
 pub async fn get_account_from_db(
-    db_conn: &DatabaseConnection,
+    db_conn: &impl ConnectionTrait,
     realm_id: i32,
     player_hash: i64,
-) -> Result<Option<AccountModel>, DbErr> {
+) -> Result<Option<AccountModel>, ProfileServerError> {
     // get the account by (realm_id, player_hash)
-    let account = Account::find_by_id((realm_id, player_hash))
+    let Some(mut account) = Account::find_by_id((realm_id, player_hash))
         .one(db_conn)
-        .await?;
-    Ok(account)
+        .await?
+    else {
+        return Ok(None);
+    };
+    load_account_items(db_conn, std::slice::from_mut(&mut account)).await?;
+    Ok(Some(account))
 }
 
 pub async fn get_account(
@@ -293,7 +596,9 @@ pub async fn get_account(
     player: &Arc<PlayerModel>,
 ) -> Result<Option<Arc<AccountModel>>, ProfileServerError> {
     // search for account in cache
-    match state.cache.accounts.get(&(realm.id, player.hash)) {
+    let cached = state.cache.accounts.get(&(realm.id, player.hash));
+    state.metrics.observe_cache_lookup("accounts", cached.is_some());
+    match cached {
         Some(account) => {
             tracing::debug!(
                 "found account ('{}','{}') in cache",
@@ -333,6 +638,77 @@ pub async fn get_account(
     }
 }
 
+pub async fn upsert_account(
+    db_conn: &impl ConnectionTrait,
+    account: &AccountModel,
+    item_storage: ItemStorage,
+) -> Result<(), ProfileServerError> {
+    let stored_account = store_account_items(db_conn, std::slice::from_ref(account), item_storage)
+        .await?
+        .remove(0);
+    Account::insert(stored_account.into_active_model().reset_all())
+        .on_conflict(
+            OnConflict::columns([AccountColumn::RealmId, AccountColumn::Hash])
+                .update_columns(ACCOUNT_COLUMNS)
+                .to_owned(),
+        )
+        .exec(db_conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_accounts_from_db(
+    db_conn: &DatabaseConnection,
+    player_hash: i64,
+    realm_id: Option<i32>,
+) -> Result<Vec<i32>, DbErr> {
+    // hand back the realms that lost an account so that the caller can invalidate any cached copies
+    let mut query = Account::find().filter(AccountColumn::Hash.eq(player_hash));
+    if let Some(realm_id) = realm_id {
+        query = query.filter(AccountColumn::RealmId.eq(realm_id));
+    }
+    let txn = db_conn.begin().await?;
+    let realm_ids: Vec<i32> = query.all(&txn).await?.iter().map(|account| account.realm_id).collect();
+    delete_account_items(&txn, player_hash, realm_id).await?;
+    Account::delete_many()
+        .filter(AccountColumn::Hash.eq(player_hash))
+        .filter(AccountColumn::RealmId.is_in(realm_ids.clone()))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(realm_ids)
+}
+
+pub async fn delete_player_from_db(
+    db_conn: &DatabaseConnection,
+    player_hash: i64,
+) -> Result<Vec<i32>, DbErr> {
+    let txn = db_conn.begin().await?;
+    let realm_ids: Vec<i32> = Account::find()
+        .filter(AccountColumn::Hash.eq(player_hash))
+        .all(&txn)
+        .await?
+        .iter()
+        .map(|account| account.realm_id)
+        .collect();
+    delete_account_items(&txn, player_hash, None).await?;
+    Account::delete_many()
+        .filter(AccountColumn::Hash.eq(player_hash))
+        .exec(&txn)
+        .await?;
+    AccountHistory::delete_many()
+        .filter(AccountHistoryColumn::Hash.eq(player_hash))
+        .exec(&txn)
+        .await?;
+    AccountAnomaly::delete_many()
+        .filter(AccountAnomalyColumn::Hash.eq(player_hash))
+        .exec(&txn)
+        .await?;
+    Player::delete_by_id(player_hash).exec(&txn).await?;
+    txn.commit().await?;
+    Ok(realm_ids)
+}
+
 pub async fn enlist_player(
     state: &AppState,
     params: &GetProfileParams,
@@ -379,26 +755,31 @@ pub fn make_init_profile_xml(username: &str, rid: &str) -> Result<String, Profil
     Ok(result)
 }
 
+#[allow(clippy::partialeq_to_none)]
 pub fn make_account_model(
     realm_id: i32,
     player_xml: &PlayerXml,
 ) -> Result<AccountActiveModel, ProfileServerError> {
     // process loadout, backpack and stash
     let loadout = Loadout::new(&player_xml.person.equipped_items);
-    let loadout_json = serde_json::to_string(&loadout)?;
+    let loadout_json = to_blob(&loadout)?;
     let backpack_store = ItemStore::new(&player_xml.person.backpack.items);
-    let backpack_json = serde_json::to_string(&backpack_store)?;
+    let backpack_json = to_blob(&backpack_store)?;
     let stash_store = ItemStore::new(&player_xml.person.stash.items);
-    let stash_json = serde_json::to_string(&stash_store)?;
+    let stash_json = to_blob(&stash_store)?;
     // process monitors
     let mut longest_death_steak = 0;
-    let mut kill_combo_json = String::new();
+    let mut kill_combo_json = to_blob(&KillCombos::empty())?;
     let mut monitors: Vec<CriteriaMonitor> = Vec::new();
-    for monitor_xml in &player_xml.profile.stats.monitors {
+    for (position, monitor_xml) in player_xml.profile.stats.monitors.iter().enumerate() {
+        if player_xml.extras.raw_monitors.contains(&position) {
+            // kept whole in the extras instead
+            continue;
+        }
         if monitor_xml.name == Some(String::from("kill combo")) {
             // process the kill combo monitor
             let kill_combo = KillCombos::new(&monitor_xml.entries);
-            kill_combo_json = serde_json::to_string(&kill_combo)?;
+            kill_combo_json = to_blob(&kill_combo)?;
         } else if monitor_xml.name == Some(String::from("death streak")) {
             // process the death streak monitor
             longest_death_steak = monitor_xml.longest_death_streak.unwrap_or(0);
@@ -412,7 +793,7 @@ pub fn make_account_model(
         }
     }
     let criteria_monitors = CriteriaMonitors { monitors };
-    let criteria_monitors_json = serde_json::to_string(&criteria_monitors)?;
+    let criteria_monitors_json = to_blob(&criteria_monitors)?;
 
     // make an active account model
     let account_model = AccountActiveModel {
@@ -447,6 +828,7 @@ pub fn make_account_model(
         longest_death_streak: ActiveValue::Set(longest_death_steak),
         kill_combos: ActiveValue::Set(kill_combo_json),
         criteria_monitors: ActiveValue::Set(criteria_monitors_json),
+        extras: ActiveValue::Set(to_blob(&player_xml.extras)?),
     };
     Ok(account_model)
 }
@@ -457,7 +839,135 @@ pub fn make_account_xml(
 ) -> Result<String, ProfileServerError> {
     let data = GetProfileDataXml::new(player, account)?;
     let serializer = QuickXmlSerializer::with_root(String::new(), Some("data"))?;
-    let mut xml = data.serialize(serializer)?;
+    let mut xml = write_extras(&data.serialize(serializer)?, &data.extras)?;
     xml.push('\n');
     Ok(xml)
 }
+
+pub fn make_account_json(
+    player: &Arc<PlayerModel>,
+    account: &Arc<AccountModel>,
+) -> Result<serde_json::Value, ProfileServerError> {
+    // mirror the xml the game server would get, minus the attribute markers
+    let data = GetProfileDataXml::new(player, account)?;
+    let mut json = serde_json::to_value(data)?;
+    strip_attribute_markers(&mut json);
+    Ok(json)
+}
+
+fn strip_attribute_markers(json: &mut serde_json::Value) {
+    match json {
+        serde_json::Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    strip_attribute_markers(&mut value);
+                    (key.trim_start_matches('@').to_owned(), value)
+                })
+                .collect();
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(strip_attribute_markers),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::config::AppConfiguration;
+    use crate::app::testing::{account_model, insert_player, insert_realm, sqlite_db, test_dbs, vanilla_player_xml};
+
+    #[tokio::test]
+    async fn upserted_accounts_read_back_unchanged() {
+        for db in test_dbs().await {
+            let realm = insert_realm(&db, "INCURSION").await;
+            insert_player(&db, 1234, "FIXTURE").await;
+            let mut account = account_model(realm.id, 1234);
+            upsert_account(&db, &account, ItemStorage::Json).await.unwrap();
+            assert_eq!(get_account_from_db(&db, realm.id, 1234).await.unwrap(), Some(account.clone()));
+
+            // the second upsert lands on the conflict path and must update the row in place
+            account.kills += 10;
+            account.distance_moved = 0.1;
+            account.stash = String::from(r#"{"v":1,"items":[]}"#);
+            upsert_account(&db, &account, ItemStorage::Json).await.unwrap();
+            assert_eq!(get_account_from_db(&db, realm.id, 1234).await.unwrap(), Some(account));
+            assert_eq!(Account::find().count(&db).await.unwrap(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn realm_capacity_is_counted_once_and_kept_up() {
+        let state = AppState::new(AppConfiguration::default(), sqlite_db().await, None);
+        let realm = insert_realm(&state.db, "INCURSION").await;
+        let realm_config = RealmConfiguration {
+            name: String::from("INCURSION"),
+            max_players: Some(2),
+            ..Default::default()
+        };
+        insert_player(&state.db, 1, "FIXTURE1").await;
+        upsert_account(&state.db, &account_model(realm.id, 1), ItemStorage::Json).await.unwrap();
+        check_realm_has_capacity(&state, &realm_config, realm.id).await.unwrap();
+
+        // once counted, the realm fills up from the accounts set_profile reports without going back to the db
+        insert_player(&state.db, 2, "FIXTURE2").await;
+        upsert_account(&state.db, &account_model(realm.id, 2), ItemStorage::Json).await.unwrap();
+        check_realm_has_capacity(&state, &realm_config, realm.id).await.unwrap();
+        count_new_realm_accounts(&state, realm.id, 1);
+        let err = check_realm_has_capacity(&state, &realm_config, realm.id).await.unwrap_err();
+        assert!(matches!(err, ProfileServerError::RealmFull(_, 2)));
+
+        // deleting an account through the caches has the realm counted again
+        delete_accounts_from_db(&state.db, 2, Some(realm.id)).await.unwrap();
+        state.cache.invalidate_account(realm.id, 2).await;
+        check_realm_has_capacity(&state, &realm_config, realm.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn wrong_realm_digests_are_checked_against_the_cache() {
+        let state = AppState::new(AppConfiguration::default(), sqlite_db().await, None);
+        let realm_config = RealmConfiguration { name: String::from("INCURSION"), ..Default::default() };
+        let client_ip = "127.0.0.1".parse().unwrap();
+        let (old_digest, new_digest) = ("a".repeat(64), "b".repeat(64));
+        let realm_lock = get_realm(&state, &realm_config, &old_digest, client_ip).await.unwrap();
+        let realm = realm_lock.read().await.clone();
+
+        // a grace period that can't be added to now is refused rather than panicking
+        let err = rotate_realm_digest(&state.db, realm.clone(), &new_digest, Duration::max_value(), "test").await;
+        assert!(matches!(err, Err(DbErr::Custom(_))));
+        // rotated behind the server's back, e.g. by the cli
+        rotate_realm_digest(&state.db, realm, &new_digest, Duration::hours(1), "test").await.unwrap();
+        // until the refresh lapses a digest the cache doesn't know about is turned away without a trip to the db
+        let err = get_realm(&state, &realm_config, &new_digest, client_ip).await.unwrap_err();
+        assert!(matches!(err, ProfileServerError::RealmDigestIncorrect(..)));
+        get_realm(&state, &realm_config, &old_digest, client_ip).await.unwrap();
+
+        state.cache.realm_refreshes.invalidate("INCURSION").await;
+        get_realm(&state, &realm_config, &new_digest, client_ip).await.unwrap();
+        // the outgoing digest is still good for the grace window
+        get_realm(&state, &realm_config, &old_digest, client_ip).await.unwrap();
+        let err = get_realm(&state, &realm_config, &"c".repeat(64), client_ip).await.unwrap_err();
+        assert!(matches!(err, ProfileServerError::RealmDigestIncorrect(..)));
+    }
+
+    #[test]
+    fn player_xml_must_agree_with_itself() {
+        let player_xml = vanilla_player_xml();
+        assert!(check_player_xml_identity(&player_xml).is_ok());
+
+        // someone else's save under this player's username
+        let wrong_hash = PlayerXml { hash: 1234, ..vanilla_player_xml() };
+        let username_hash = rwr1_hash_username(&player_xml.profile.username);
+        assert!(matches!(
+            check_player_xml_identity(&wrong_hash),
+            Err(ProfileServerError::PlayerHashMismatch(1234, username, hash))
+                if username == player_xml.profile.username && hash == username_hash
+        ));
+
+        let wrong_rid = PlayerXml { rid: "f".repeat(64), ..vanilla_player_xml() };
+        assert!(matches!(
+            check_player_xml_identity(&wrong_rid),
+            Err(ProfileServerError::PlayerRidMismatch(3235692716, username)) if username == player_xml.profile.username
+        ));
+    }
+}
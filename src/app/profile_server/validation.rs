@@ -12,6 +12,7 @@ use validator::Validate;
 
 use super::super::hasher::rwr1_hash_username;
 use super::errors::ProfileServerError;
+use super::extras::CaptureExtras;
 use super::params::GetProfileParams;
 use super::util::USERNAME_BLOCKED_CHARS;
 
@@ -44,7 +45,7 @@ pub struct ValidatedXmlBody<T>(pub T);
 #[async_trait]
 impl<T, S, B> FromRequest<S, B> for ValidatedXmlBody<T>
 where
-    T: DeserializeOwned + Validate,
+    T: DeserializeOwned + Validate + CaptureExtras,
     B: HttpBody + Send + 'static,
     B::Data: Send,
     B::Error: Into<BoxError>,
@@ -60,7 +61,9 @@ where
         // tracing::debug!("{xml_str:#?}");
         let decoded_xml_str = percent_decode_str(xml_str).decode_utf8()?;
         // tracing::debug!("{decoded_xml_str}");
-        let data: T = quick_xml::de::from_str(decoded_xml_str.as_ref())?;
+        let mut data: T = quick_xml::de::from_str(decoded_xml_str.as_ref())?;
+        // keep whatever the structs have no place for, so that it can be sent back on the next get
+        data.capture_extras(decoded_xml_str.as_ref())?;
         // validate the xml data
         data.validate()?;
         Ok(Self(data))
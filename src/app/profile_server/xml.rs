@@ -5,13 +5,12 @@ use validator::Validate;
 
 use super::{
     errors::ProfileServerError,
-    json::{CriteriaMonitors, ItemStore, KillCombos, Loadout},
+    extras::AccountExtras,
+    json::{from_blob, CriteriaMonitors, ItemStore, KillCombos, Loadout},
     validation::{validate_username, RE_HEX_STR},
 };
 use entity::{AccountModel, PlayerModel};
 
-// todo: validate that hash for player username
-
 #[derive(Debug, Deserialize, Validate)]
 pub struct SetProfileDataXml {
     #[serde(rename = "player")]
@@ -32,6 +31,8 @@ pub struct PlayerXml {
     pub person: PersonXml,
     #[validate]
     pub profile: ProfileXml,
+    #[serde(skip)]
+    pub extras: AccountExtras,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
@@ -200,8 +201,11 @@ pub struct CriteriaXml {
 pub struct GetProfileDataXml {
     #[serde(rename = "@ok")]
     ok: i32,
-    profile: ProfileXml,
-    person: PersonXml,
+    pub profile: ProfileXml,
+    pub person: PersonXml,
+    // written into the serialized xml afterwards, see write_extras
+    #[serde(skip)]
+    pub extras: AccountExtras,
 }
 
 impl GetProfileDataXml {
@@ -209,13 +213,14 @@ impl GetProfileDataXml {
         player: &Arc<PlayerModel>,
         account: &Arc<AccountModel>,
     ) -> Result<Self, ProfileServerError> {
-        let loadout_json: Loadout = serde_json::from_str(&account.loadout)?;
-        let backpack_json: ItemStore = serde_json::from_str(&account.backpack)?;
-        let stash_json: ItemStore = serde_json::from_str(&account.stash)?;
+        // older blobs are brought up to the current schema as they are read
+        let loadout_json: Loadout = from_blob(&account.loadout)?;
+        let backpack_json: ItemStore = from_blob(&account.backpack)?;
+        let stash_json: ItemStore = from_blob(&account.stash)?;
         // construct monitors
-        let kill_combos_json: KillCombos = serde_json::from_str(&account.kill_combos)?;
-        let criteria_monitors_json: CriteriaMonitors =
-            serde_json::from_str(&account.criteria_monitors)?;
+        let kill_combos_json: KillCombos = from_blob(&account.kill_combos)?;
+        let criteria_monitors_json: CriteriaMonitors = from_blob(&account.criteria_monitors)?;
+        let extras: AccountExtras = from_blob(&account.extras)?;
 
         let mut monitors = Vec::new();
         // insert the longest death streak monitor
@@ -300,6 +305,7 @@ impl GetProfileDataXml {
                     monitors,
                 },
             },
+            extras,
         })
     }
 }
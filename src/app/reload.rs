@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use super::config::{AppConfiguration, CONFIG_FILE};
+use super::profile_server::util::seed_configured_realms;
+use super::state::AppState;
+
+// while config_watch_secs = 0, how often to check whether a reload on SIGHUP has turned the watch back on
+const WATCH_DISABLED_RECHECK: Duration = Duration::from_secs(60);
+
+// these are only read while starting up, a changed value is kept back (with a warning) until the next restart
+const RESTART_ONLY: [&str; 11] = [
+    "listen_addrs",
+    "listen_port",
+    "db_url",
+    "proxy_protocol",
+    "log_format",
+    "log_file",
+    "log_file_format",
+    "log_file_rotation",
+    "log_file_keep",
+    "ps_item_catalog",
+    // the items have to be moved over with the move-items subcommand whilst the server is stopped
+    "ps_item_storage",
+];
+
+// watches CONFIG_FILE for changes (and SIGHUP on unix) and reloads the configuration when either happens
+pub fn spawn_config_reload(state: AppState) {
+    // a reload from the file and one from a signal must not interleave
+    let reloading = Arc::new(Mutex::new(()));
+
+    let watch_state = state.clone();
+    let watch_reloading = reloading.clone();
+    tokio::spawn(async move {
+        let mut last_modified = modified(CONFIG_FILE);
+        loop {
+            // read every time round, so that a reload can change how often we look
+            let watch_secs = watch_state.config().config_watch_secs;
+            if watch_secs == 0 {
+                tokio::time::sleep(WATCH_DISABLED_RECHECK).await;
+                last_modified = modified(CONFIG_FILE);
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(watch_secs)).await;
+            let current_modified = modified(CONFIG_FILE);
+            if current_modified == last_modified {
+                continue;
+            }
+            last_modified = current_modified;
+            let _reloading = watch_reloading.lock().await;
+            reload_config(&watch_state, &format!("{CONFIG_FILE} changed")).await;
+        }
+    });
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(err) => {
+                tracing::error!("failed to install the SIGHUP handler, config reloads on SIGHUP are off: {err}");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            let _reloading = reloading.lock().await;
+            reload_config(&state, "SIGHUP received").await;
+        }
+    });
+}
+
+fn modified(path: impl AsRef<Path>) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// loads and validates the configuration again, swapping it in only if it is valid
+pub async fn reload_config(state: &AppState, reason: &str) {
+    tracing::info!("{reason}, reloading the configuration...");
+    let running = state.config();
+    let mut candidate = match AppConfiguration::load() {
+        Ok(candidate) => candidate,
+        Err(err) => {
+            tracing::error!("rejected the new configuration, keeping the running one: {err}");
+            return;
+        }
+    };
+    keep_restart_only(&running, &mut candidate);
+
+    let changes = diff(&running, &candidate);
+    if let Err(err) = candidate.validate() {
+        tracing::error!("rejected the new configuration, keeping the running one: {err}");
+        for change in changes.iter() {
+            tracing::error!("  rejected: {change}");
+        }
+        return;
+    }
+    if changes.is_empty() {
+        tracing::info!("the configuration is unchanged");
+        return;
+    }
+    for change in changes.iter() {
+        tracing::info!("  applied: {change}");
+    }
+
+    // resolve any new hostnames before the rules that refer to them are in place
+    state.resolver.set_hosts(candidate.hostnames()).await;
+    state.swap_config(candidate);
+    if let Err(err) = seed_configured_realms(state).await {
+        tracing::error!("failed to seed the realms pinned in the new configuration: {err}");
+    }
+    tracing::info!("reloaded the configuration with {} change(s)", changes.len());
+}
+
+fn keep_restart_only(running: &AppConfiguration, candidate: &mut AppConfiguration) {
+    let (Ok(Value::Object(running_fields)), Ok(Value::Object(mut candidate_fields))) =
+        (serde_json::to_value(running), serde_json::to_value(&*candidate))
+    else {
+        return;
+    };
+    let mut kept = false;
+    for key in RESTART_ONLY {
+        let running_value = running_fields.get(key).cloned().unwrap_or(Value::Null);
+        if candidate_fields.get(key) != Some(&running_value) {
+            tracing::warn!("'{key}' has changed but only takes effect after a restart");
+            candidate_fields.insert(key.to_owned(), running_value);
+            kept = true;
+        }
+    }
+    if kept {
+        match serde_json::from_value(Value::Object(candidate_fields)) {
+            Ok(restored) => *candidate = restored,
+            Err(err) => tracing::error!("failed to keep the restart-only settings: {err}"),
+        }
+    }
+    // the admin api and /metrics are mounted (or not) at startup, so turning them on or off needs a restart too
+    if running.admin_token.is_some() != candidate.admin_token.is_some() {
+        tracing::warn!("'admin_token' has been set or unset, the admin api is only mounted or unmounted on restart");
+    }
+    if running.metrics_allowed_ips.is_empty() != candidate.metrics_allowed_ips.is_empty() {
+        tracing::warn!("'metrics_allowed_ips' has been emptied or filled, /metrics is only mounted or unmounted on restart");
+    }
+}
+
+// each setting that differs, as "key: old -> new" with the keys flattened to dotted paths
+fn diff(running: &AppConfiguration, candidate: &AppConfiguration) -> Vec<String> {
+    let mut running_fields = BTreeMap::new();
+    let mut candidate_fields = BTreeMap::new();
+    flatten("", serde_json::to_value(running).unwrap_or_default(), &mut running_fields);
+    flatten("", serde_json::to_value(candidate).unwrap_or_default(), &mut candidate_fields);
+    let mut keys: Vec<&String> = running_fields.keys().chain(candidate_fields.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = running_fields.get(key).unwrap_or(&Value::Null);
+            let new = candidate_fields.get(key).unwrap_or(&Value::Null);
+            (old != new).then(|| format!("{key}: {} -> {}", redact(key, old), redact(key, new)))
+        })
+        .collect()
+}
+
+fn flatten(prefix: &str, value: Value, fields: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let key = if prefix.is_empty() { key } else { format!("{prefix}.{key}") };
+                flatten(&key, value, fields);
+            }
+        }
+        // the sets come out in hash order, sort them so that only real changes show up
+        Value::Array(mut items) => {
+            items.sort_by_key(|item| item.to_string());
+            fields.insert(prefix.to_owned(), Value::Array(items));
+        }
+        // a setting left out and one set to nothing are the same thing
+        Value::Null => {}
+        value => {
+            fields.insert(prefix.to_owned(), value);
+        }
+    }
+}
+
+fn redact(key: &str, value: &Value) -> String {
+    match value {
+        Value::Null => String::from("(unset)"),
+        _ if key == "admin_token" => String::from("<redacted>"),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_only_settings_keep_their_running_values() {
+        let running = AppConfiguration::default();
+        let mut candidate = AppConfiguration {
+            listen_port: running.listen_port + 1,
+            db_url: String::from("sqlite://elsewhere.db?mode=rwc"),
+            ps_strict_realms: !running.ps_strict_realms,
+            ..Default::default()
+        };
+        keep_restart_only(&running, &mut candidate);
+        assert_eq!(candidate.listen_port, running.listen_port);
+        assert_eq!(candidate.db_url, running.db_url);
+        // everything else is reloaded as usual
+        assert_eq!(candidate.ps_strict_realms, !running.ps_strict_realms);
+    }
+
+    #[test]
+    fn diffs_hide_the_admin_token_and_ignore_set_order() {
+        let running = AppConfiguration {
+            admin_token: Some("a".repeat(32)),
+            ps_allowed_sids: (0..64).collect(),
+            ..Default::default()
+        };
+        // the same sids inserted the other way round, in a set of its own capacity
+        let mut reordered = std::collections::HashSet::with_capacity(1024);
+        reordered.extend((0..64).rev());
+        let candidate = AppConfiguration {
+            admin_token: Some("b".repeat(32)),
+            ps_allowed_sids: reordered,
+            ..Default::default()
+        };
+        assert_eq!(diff(&running, &candidate), vec!["admin_token: <redacted> -> <redacted>"]);
+
+        let candidate = AppConfiguration { admin_token: None, ..running.clone() };
+        assert_eq!(diff(&running, &candidate), vec!["admin_token: <redacted> -> (unset)"]);
+        assert!(diff(&running, &running.clone()).is_empty());
+    }
+}
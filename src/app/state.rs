@@ -1,18 +1,31 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, RwLock as StdRwLock};
 use std::time::Duration;
 
-use moka::future::Cache;
+use moka::future::{Cache, ConcurrentCacheExt};
 use sea_orm::DatabaseConnection;
 use tokio::sync::RwLock;
 
+use super::catalog::ItemCatalog;
+use super::ip_rules::HostResolver;
+use super::metrics::Metrics;
+use super::profile_server::rate_limit::RateLimiter;
 use crate::AppConfiguration;
-use entity::{AccountModel, PlayerModel, RealmModel};
+use entity::{AccountModel, BanModel, PlayerModel, RealmModel};
 
 #[derive(Clone)]
 pub struct CacheManager {
     pub realms: Cache<String, Arc<RwLock<RealmModel>>>,
+    // the realms reloaded from the db in the last minute, so that a digest rotated by the cli is soon picked up
+    // without a request ever being able to make us go to the db more often than that
+    pub realm_refreshes: Cache<String, ()>,
     pub players: Cache<i64, Arc<PlayerModel>>,
     pub accounts: Cache<(i32, i64), Arc<AccountModel>>,
+    // every ban in force, kept briefly so that bans made by the cli (or another instance) are soon picked up
+    pub bans: Cache<(), Arc<Vec<BanModel>>>,
+    // the number of accounts in each realm, counted once and then kept up as set_profile adds accounts, the entries
+    // expire so that accounts deleted or imported by the cli (or another instance) are soon counted in
+    pub realm_accounts: Cache<i32, Arc<AtomicU64>>,
 }
 
 impl Default for CacheManager {
@@ -39,6 +52,12 @@ impl Default for CacheManager {
                         drop(value);
                     })
                     .build(),
+            realm_refreshes:
+                Cache::builder()
+                    .name("realm_refreshes")
+                    .max_capacity(32)
+                    .time_to_live(Duration::from_secs(60))
+                    .build(),
             players:
                 Cache::builder()
                     .name("players")
@@ -77,29 +96,104 @@ impl Default for CacheManager {
                         }
                     })
                     .build(),
+            bans:
+                Cache::builder()
+                    .name("bans")
+                    .max_capacity(1)
+                    .time_to_live(Duration::from_secs(30))
+                    .build(),
+            realm_accounts:
+                Cache::builder()
+                    .name("realm_accounts")
+                    .max_capacity(32)
+                    .time_to_live(Duration::from_secs(5*60))
+                    .build(),
+        }
+    }
+}
+
+impl CacheManager {
+    pub async fn invalidate_player(&self, player_hash: i64, realm_ids: &[i32]) {
+        self.players.invalidate(&player_hash).await;
+        for realm_id in realm_ids {
+            self.invalidate_account(*realm_id, player_hash).await;
         }
     }
+
+    pub async fn invalidate_account(&self, realm_id: i32, player_hash: i64) {
+        self.accounts.invalidate(&(realm_id, player_hash)).await;
+        // the account may well have been deleted, have the realm's accounts counted again
+        self.realm_accounts.invalidate(&realm_id).await;
+    }
+
+    pub fn entry_counts(&self) -> [(&'static str, u64); 5] {
+        // entry counts lag behind until the caches have applied their pending writes
+        self.realms.sync();
+        self.players.sync();
+        self.accounts.sync();
+        self.bans.sync();
+        self.realm_accounts.sync();
+        [
+            ("realms", self.realms.entry_count()),
+            ("players", self.players.entry_count()),
+            ("accounts", self.accounts.entry_count()),
+            ("bans", self.bans.entry_count()),
+            ("realm_accounts", self.realm_accounts.entry_count()),
+        ]
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
-    pub config: AppConfiguration,
+    // swapped wholesale when the configuration is reloaded, see config()
+    config: Arc<StdRwLock<Arc<AppConfiguration>>>,
     pub db: DatabaseConnection,
     pub cache: CacheManager,
+    pub resolver: HostResolver,
+    pub item_catalog: Option<Arc<ItemCatalog>>,
+    pub metrics: Arc<Metrics>,
+    pub rate_limiter: Arc<RateLimiter>,
+    // set once the shutdown signal has been received and the listeners are draining
+    pub draining: Arc<AtomicBool>,
 }
 
 impl AppState {
-    pub fn new(app_config: AppConfiguration, db_conn: DatabaseConnection) -> Self {
+    pub fn new(
+        app_config: AppConfiguration,
+        mut db_conn: DatabaseConnection,
+        item_catalog: Option<ItemCatalog>,
+    ) -> Self {
+        // time every query the connection makes, clones of the connection share the callback
+        let metrics = Arc::new(Metrics::default());
+        let query_metrics = metrics.clone();
+        db_conn.set_metric_callback(move |info| query_metrics.observe_query(info));
         Self {
-            config: app_config,
+            config: Arc::new(StdRwLock::new(Arc::new(app_config))),
             db: db_conn,
             cache: CacheManager::default(),
+            resolver: HostResolver::default(),
+            item_catalog: item_catalog.map(Arc::new),
+            metrics,
+            rate_limiter: Arc::new(RateLimiter::default()),
+            draining: Arc::new(AtomicBool::new(false)),
         }
     }
 }
 
+impl AppState {
+    // the configuration as it is right now, hold on to it for a whole request so that a reload can't land midway
+    pub fn config(&self) -> Arc<AppConfiguration> {
+        self.config.read().unwrap().clone()
+    }
+
+    // puts a new configuration in place for every request from here on, handing back the one it replaced
+    pub fn swap_config(&self, app_config: AppConfiguration) -> Arc<AppConfiguration> {
+        std::mem::replace(&mut *self.config.write().unwrap(), Arc::new(app_config))
+    }
+}
+
 impl std::fmt::Debug for AppState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "AppState {{ config: {:#?} }}", self.config)
+        write!(f, "AppState {{ config: {:#?} }}", self.config())
     }
 }
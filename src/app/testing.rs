@@ -0,0 +1,113 @@
+// shared setup for the tests, every test that needs a database gets a freshly migrated one of its own
+use std::fs;
+use std::path::PathBuf;
+
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectOptions, Database, DatabaseConnection};
+
+use super::profile_server::xml::{PlayerXml, ProfileXml};
+use entity::{AccountModel, PlayerActiveModel, PlayerModel, RealmActiveModel, RealmModel};
+use migration::{Migrator, MigratorTrait};
+
+// set this to a postgres:// url (of a database that can be wiped) to also run the database tests against postgres,
+// each test starts by wiping it so run them with --test-threads=1
+pub const POSTGRES_URL_VAR: &str = "MRWR_TEST_POSTGRES_URL";
+
+pub async fn sqlite_db() -> DatabaseConnection {
+    // every connection to sqlite::memory: opens a database of its own, so the pool must stick to the one
+    let mut options = ConnectOptions::new(String::from("sqlite::memory:"));
+    options.max_connections(1).min_connections(1).sqlx_logging(false);
+    let db = Database::connect(options).await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    db
+}
+
+pub async fn postgres_db() -> Option<DatabaseConnection> {
+    let url = std::env::var(POSTGRES_URL_VAR).ok()?;
+    let mut options = ConnectOptions::new(url);
+    options.sqlx_logging(false);
+    let db = Database::connect(options).await.unwrap();
+    Migrator::fresh(&db).await.unwrap();
+    Some(db)
+}
+
+// the databases to run a test against, postgres only when POSTGRES_URL_VAR is set
+pub async fn test_dbs() -> Vec<DatabaseConnection> {
+    let mut dbs = vec![sqlite_db().await];
+    if let Some(db) = postgres_db().await {
+        dbs.push(db);
+    }
+    dbs
+}
+
+pub async fn insert_realm(db: &DatabaseConnection, name: &str) -> RealmModel {
+    let realm = RealmActiveModel {
+        name: ActiveValue::Set(name.to_owned()),
+        digest: ActiveValue::Set("0".repeat(64)),
+        ..Default::default()
+    };
+    realm.insert(db).await.unwrap()
+}
+
+pub async fn insert_player(db: &DatabaseConnection, hash: i64, username: &str) -> PlayerModel {
+    let player = PlayerActiveModel {
+        hash: ActiveValue::Set(hash),
+        username: ActiveValue::Set(username.to_owned()),
+        sid: ActiveValue::Set(hash),
+        rid: ActiveValue::Set("0".repeat(64)),
+    };
+    player.insert(db).await.unwrap()
+}
+
+pub fn account_model(realm_id: i32, hash: i64) -> AccountModel {
+    AccountModel {
+        realm_id,
+        hash,
+        game_version: 143,
+        squad_tag: String::from("ABC"),
+        max_authority_reached: 12.5,
+        authority: 10.25,
+        job_points: 3.125,
+        faction: 0,
+        name: String::from("FIXTURE"),
+        soldier_group_id: 0,
+        soldier_group_name: String::from("default"),
+        squad_size_setting: 4,
+        loadout: String::from(r#"{"v":1,"slots":[{"s":0,"i":3,"k":"ak47.weapon","a":1},{"s":1,"i":-1,"k":"","a":0},{"s":2,"i":1,"k":"hand_grenade.projectile","a":2},{"s":4,"i":0,"k":"vest2.carry_item","a":1}]}"#),
+        backpack: String::from(r#"{"v":1,"items":[{"c":0,"i":3,"k":"m16a4.weapon","a":2}]}"#),
+        stash: String::from(r#"{"v":1,"items":[{"c":3,"i":0,"k":"vest1.carry_item","a":5}]}"#),
+        kills: 120,
+        deaths: 30,
+        time_played: 7200,
+        player_kills: 2,
+        teamkills: 1,
+        longest_kill_streak: 17,
+        targets_destroyed: 3,
+        vehicles_destroyed: 4,
+        soldiers_healed: 5,
+        distance_moved: 12345.5,
+        shots_fired: 2000,
+        throwables_thrown: 12,
+        rank_progression: 0.75,
+        longest_death_streak: 3,
+        kill_combos: String::from(r#"{"v":1,"entries":[[2,3],[3,1]]}"#),
+        criteria_monitors: String::from(r#"{"v":1,"monitors":[{"n":"squad tag","l":1,"c":[4,5]}]}"#),
+        extras: String::from(r#"{"v":1}"#),
+    }
+}
+
+pub fn fixture_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test/fixtures").join(path)
+}
+
+// the player in test/fixtures/vanilla, as it would arrive in a set xml
+pub fn vanilla_player_xml() -> PlayerXml {
+    let read = |extension: &str| fs::read_to_string(fixture_path(&format!("vanilla/3235692716.{extension}"))).unwrap();
+    let profile: ProfileXml = quick_xml::de::from_str(&read("profile")).unwrap();
+    PlayerXml {
+        hash: 3235692716,
+        rid: profile.rid.to_owned(),
+        person: quick_xml::de::from_str(&read("person")).unwrap(),
+        profile,
+        extras: Default::default(),
+    }
+}
@@ -1,41 +1,113 @@
 use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use nu_ansi_term::{Color, Style};
-use tracing::{log::Level, Event, Subscriber};
+use axum::http::Request;
+use chrono::{SecondsFormat, Utc};
+use nu_ansi_term::Style;
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Span, Subscriber};
+use tracing_subscriber::field::RecordFields;
 use tracing_subscriber::fmt::time::FormatTime;
-use tracing_subscriber::fmt::time::{SystemTime, UtcTime};
+use tracing_subscriber::fmt::time::SystemTime;
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriter};
 use tracing_subscriber::fmt::{
     format::{self, FormatEvent, FormatFields},
     FmtContext, FormattedFields,
 };
-use tracing_subscriber::registry::{LookupSpan, self};
+use tracing_subscriber::registry::{LookupSpan, Registry, self};
 use tracing_subscriber::{
     fmt::format::{FmtSpan, Writer},
     layer::SubscriberExt,
     util::SubscriberInitExt,
+    Layer,
 };
 
-pub fn init_tracing_subscriber() {
+use super::config::{AppConfiguration, LogFormat, LogRotation};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+pub fn init_tracing_subscriber(serving: bool, app_config: &AppConfiguration) -> io::Result<()> {
+    // the admin subcommands print their results to stdout, so keep their logging quiet and out of the way
+    let (default_filter, writer) = match serving {
+        true => ("marshalrwr=debug,tower_http=debug", BoxMakeWriter::new(std::io::stdout)),
+        false => ("marshalrwr=warn", BoxMakeWriter::new(std::io::stderr)),
+    };
+    let mut layers = vec![make_layer(app_config.log_format, writer, true)];
+    // only the server itself writes to the log file
+    if let (true, Some(path)) = (serving, &app_config.log_file) {
+        let log_file = RollingFile::new(path, app_config.log_file_rotation, app_config.log_file_keep)?;
+        layers.push(make_layer(app_config.log_file_format, log_file, false));
+    }
     // setup tracing subscriber first and foremost
     tracing_subscriber::registry()
+        .with(layers)
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "marshalrwr=debug,tower_http=debug".into()),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-                .event_format(ConsoleFormatter::default()),
+                .unwrap_or_else(|_| default_filter.into()),
         )
         .init();
+    Ok(())
+}
+
+fn make_layer<W>(log_format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match log_format {
+        LogFormat::Console => tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+            .with_ansi(ansi)
+            .with_writer(writer)
+            .event_format(ConsoleFormatter::default())
+            .boxed(),
+        // a span is only worth a line once it closes, by then it has all of its fields and its timings
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_ansi(false)
+            .fmt_fields(JsonFields)
+            .with_writer(writer)
+            .event_format(JsonFormatter)
+            .boxed(),
+    }
+}
+
+// the span every request is handled in, the handlers fill in the rest as they learn it
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        client_ip = tracing::field::Empty,
+        realm = tracing::field::Empty,
+        username = tracing::field::Empty,
+        hash = tracing::field::Empty,
+        sid = tracing::field::Empty,
+    )
 }
 
+// a set_profile request can carry several players, the span holds whichever one is being processed
+pub fn record_player(username: &str, hash: i64, sid: i64) {
+    let span = Span::current();
+    span.record("username", username);
+    span.record("hash", hash);
+    span.record("sid", sid);
+}
+
+// kept as upstream wrote it, including the options it doesn't act on yet
 pub struct ConsoleFormatter {
     pub display_timestamp: bool,
     pub display_target: bool,
     pub display_level: bool,
+    #[allow(dead_code)]
     pub display_thread_id: bool,
+    #[allow(dead_code)]
     pub display_thread_name: bool,
+    #[allow(dead_code)]
     pub display_filename: bool,
     pub display_line_number: bool,
     pub display_event_fields: bool,
@@ -77,6 +149,7 @@ impl ConsoleFormatter {
     }
 
     #[inline]
+    #[allow(clippy::match_ref_pats)]
     fn format_level(&self, writer: &mut Writer<'_>, level: &tracing::Level) -> fmt::Result {
         if !self.display_level {
             return Ok(());
@@ -104,6 +177,7 @@ where
     S: Subscriber + for<'a> LookupSpan<'a>,
     N: for<'a> FormatFields<'a> + 'static,
 {
+    #[allow(clippy::collapsible_if)]
     fn format_event(
         &self,
         ctx: &FmtContext<'_, S, N>,
@@ -161,3 +235,195 @@ where
         writeln!(writer)
     }
 }
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_owned(), Value::from(format!("{value:?}")));
+    }
+}
+
+// formats span fields as a json object, so that JsonFormatter can pick them back up for every event in the span
+pub struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut map = Map::new();
+        fields.record(&mut JsonVisitor(&mut map));
+        write!(writer, "{}", Value::Object(map))
+    }
+
+    fn add_fields(&self, current: &'writer mut FormattedFields<Self>, fields: &span::Record<'_>) -> fmt::Result {
+        // fields recorded later replace any earlier value rather than repeating the key
+        let mut map: Map<String, Value> = serde_json::from_str(&current.fields).unwrap_or_default();
+        fields.record(&mut JsonVisitor(&mut map));
+        current.fields = Value::Object(map).to_string();
+        Ok(())
+    }
+}
+
+// one json object per line: timestamp, level, target, message, the event's fields and those of its spans
+pub struct JsonFormatter;
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+        let message = fields.remove("message").unwrap_or_default();
+
+        // the spans are merged from the root down, so the innermost span wins on any clash
+        let mut span_fields = Map::new();
+        let mut span_names = Vec::new();
+        for span in ctx.event_scope().into_iter().flat_map(registry::Scope::from_root) {
+            span_names.push(Value::from(span.name()));
+            let exts = span.extensions();
+            if let Some(formatted) = exts.get::<FormattedFields<JsonFields>>() {
+                if let Ok(Value::Object(map)) = serde_json::from_str(&formatted.fields) {
+                    span_fields.extend(map);
+                }
+            }
+        }
+
+        let mut line = Map::new();
+        line.insert(String::from("timestamp"), Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)));
+        line.insert(String::from("level"), Value::from(metadata.level().as_str()));
+        line.insert(String::from("target"), Value::from(metadata.target()));
+        line.insert(String::from("message"), message);
+        if !fields.is_empty() {
+            line.insert(String::from("fields"), Value::Object(fields));
+        }
+        if !span_names.is_empty() {
+            line.insert(String::from("spans"), Value::Array(span_names));
+            line.insert(String::from("span"), Value::Object(span_fields));
+        }
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+// a log file that moves on to a new file every hour or day, keeping only the most recent few
+pub struct RollingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    keep: usize,
+    current: Mutex<(String, File)>,
+}
+
+impl RollingFile {
+    pub fn new(path: &Path, rotation: LogRotation, keep: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let period = period_of(rotation);
+        let file = open_log_file(&file_for(path, &period))?;
+        let rolling_file = RollingFile { path: path.to_owned(), rotation, keep, current: Mutex::new((period, file)) };
+        rolling_file.prune()?;
+        Ok(rolling_file)
+    }
+
+    fn write_line(&self, buf: &[u8]) -> io::Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let period = period_of(self.rotation);
+        if period != current.0 {
+            *current = (period.clone(), open_log_file(&file_for(&self.path, &period))?);
+            self.prune()?;
+        }
+        current.1.write_all(buf)
+    }
+
+    // remove all but the newest `keep` files, the period suffixes sort oldest first
+    fn prune(&self) -> io::Result<()> {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return Ok(());
+        };
+        if self.keep == 0 {
+            return Ok(());
+        }
+        let dir = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let mut rotated: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .map(|entry| entry.path())
+            .collect();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.keep);
+        for old_file in rotated.into_iter().take(excess) {
+            fs::remove_file(old_file)?;
+        }
+        Ok(())
+    }
+}
+
+fn period_of(rotation: LogRotation) -> String {
+    match rotation {
+        LogRotation::Never => String::new(),
+        LogRotation::Hourly => Utc::now().format("%Y-%m-%d-%H").to_string(),
+        LogRotation::Daily => Utc::now().format("%Y-%m-%d").to_string(),
+    }
+}
+
+fn file_for(path: &Path, period: &str) -> PathBuf {
+    match period.is_empty() {
+        true => path.to_owned(),
+        false => PathBuf::from(format!("{}.{period}", path.display())),
+    }
+}
+
+fn open_log_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+pub struct RollingFileWriter<'a>(&'a RollingFile);
+
+impl io::Write for RollingFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // every event arrives as a single buffer, so each line is written whole
+        self.0.write_line(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFile {
+    type Writer = RollingFileWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RollingFileWriter(self)
+    }
+}
@@ -1,6 +1,9 @@
 use async_trait::async_trait;
-use axum::extract::{rejection::QueryRejection, FromRequestParts, Query};
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts, Query};
 use axum::http::request::Parts;
+use axum::http::Request;
+use axum::Json;
 use serde::de::DeserializeOwned;
 use validator::Validate;
 
@@ -24,3 +27,23 @@ where
         Ok(ValidatedQuery(params))
     }
 }
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+    B: Send + 'static,
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<T>::from_request(request, state).await?;
+        body.validate()?;
+        Ok(ValidatedJson(body))
+    }
+}
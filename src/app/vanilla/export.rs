@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use quick_xml::se::Serializer as QuickXmlSerializer;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde::Serialize;
+
+use super::super::profile_server::account_items::load_account_items;
+use super::super::profile_server::extras::{write_extras, AccountExtras};
+use super::super::profile_server::xml::GetProfileDataXml;
+use super::{PERSON_EXTENSION, PROFILE_EXTENSION, XML_DECLARATION};
+use entity::{Account, AccountColumn, Player, RealmModel};
+
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    pub exported: Vec<(i64, String)>,
+    pub skipped: Vec<(i64, String)>,
+}
+
+pub async fn export_profiles(
+    db: &DatabaseConnection,
+    realm: &RealmModel,
+    dir: &Path,
+    force: bool,
+) -> anyhow::Result<ExportReport> {
+    fs::create_dir_all(dir)?;
+    let mut report = ExportReport::default();
+    // realms can be large, so walk the accounts a page at a time
+    let mut pages = Account::find()
+        .filter(AccountColumn::RealmId.eq(realm.id))
+        .order_by_asc(AccountColumn::Hash)
+        .find_also_related(Player)
+        .paginate(db, 256);
+    while let Some(accounts) = pages.fetch_and_next().await? {
+        let (mut accounts, players): (Vec<_>, Vec<_>) = accounts.into_iter().unzip();
+        load_account_items(db, &mut accounts).await?;
+        for (account, player) in accounts.into_iter().zip(players) {
+            let Some(player) = player else {
+                report.skipped.push((account.hash, String::from("account has no player")));
+                continue;
+            };
+            let profile_path = dir.join(format!("{}.{PROFILE_EXTENSION}", player.hash));
+            let person_path = dir.join(format!("{}.{PERSON_EXTENSION}", player.hash));
+            if !force && (profile_path.exists() || person_path.exists()) {
+                report.skipped.push((player.hash, format!("'{}' files already exist", player.username)));
+                continue;
+            }
+            let (hash, username) = (player.hash, player.username.to_owned());
+            // the vanilla files hold the same profile and person elements that get_profile sends
+            let data = GetProfileDataXml::new(&Arc::new(player), &Arc::new(account))?;
+            fs::write(&profile_path, to_vanilla_xml(&data.profile, "profile", &data.extras)?)?;
+            fs::write(&person_path, to_vanilla_xml(&data.person, "person", &data.extras)?)?;
+            report.exported.push((hash, username));
+        }
+    }
+    Ok(report)
+}
+
+fn to_vanilla_xml<T: Serialize>(element: &T, root: &str, extras: &AccountExtras) -> anyhow::Result<String> {
+    let serializer = QuickXmlSerializer::with_root(String::new(), Some(root))?;
+    let xml = write_extras(&element.serialize(serializer)?, extras)?;
+    Ok(format!("{XML_DECLARATION}\n{xml}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use quick_xml::events::{BytesStart, Event};
+    use quick_xml::Reader;
+
+    use super::super::import::import_profiles;
+    use super::*;
+    use crate::app::config::ItemStorage;
+    use crate::app::testing::{insert_realm, sqlite_db};
+
+    const FIXTURE_HASH: i64 = 3235692716;
+
+    // an element with its attributes and children put in order, so that two documents can be compared for what they
+    // say rather than how they say it (attribute order, whitespace, the order sibling elements come in)
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Element {
+        name: String,
+        attributes: BTreeMap<String, String>,
+        children: Vec<Element>,
+    }
+
+    impl Element {
+        fn new(start: &BytesStart) -> Self {
+            let attributes = start
+                .attributes()
+                .map(|attribute| {
+                    let attribute = attribute.unwrap();
+                    let key = String::from_utf8(attribute.key.as_ref().to_vec()).unwrap();
+                    let value = attribute.unescape_value().unwrap().into_owned();
+                    // numbers are compared as numbers, "7200.5" and "7200.50" say the same thing
+                    let value = match value.parse::<f64>() {
+                        Ok(number) => number.to_string(),
+                        Err(_) => value,
+                    };
+                    (key, value)
+                })
+                .collect();
+            Self { name: String::from_utf8(start.name().as_ref().to_vec()).unwrap(), attributes, children: Vec::new() }
+        }
+    }
+
+    fn parse(xml: &str) -> Element {
+        let mut reader = Reader::from_str(xml);
+        let mut stack: Vec<Element> = vec![Element::new(&BytesStart::new("document"))];
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(start) => stack.push(Element::new(&start)),
+                Event::Empty(start) => stack.last_mut().unwrap().children.push(Element::new(&start)),
+                Event::End(_) => {
+                    let mut element = stack.pop().unwrap();
+                    element.children.sort();
+                    stack.last_mut().unwrap().children.push(element);
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        stack.pop().unwrap()
+    }
+
+    fn fixture_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test/fixtures/vanilla")
+    }
+
+    async fn round_trip(item_storage: ItemStorage) {
+        let db = sqlite_db().await;
+        let realm = insert_realm(&db, "INCURSION").await;
+        let import_report = import_profiles(&db, &realm, &fixture_dir(), false, false, item_storage).await.unwrap();
+        assert_eq!(import_report.imported, vec![(FIXTURE_HASH, String::from("FIXTURE"))]);
+        assert!(import_report.conflicts.is_empty(), "{:?}", import_report.conflicts);
+
+        let export_dir = std::env::temp_dir().join(format!("marshalrwr-export-{}-{item_storage:?}", std::process::id()));
+        let export_report = export_profiles(&db, &realm, &export_dir, true).await.unwrap();
+        assert_eq!(export_report.exported, vec![(FIXTURE_HASH, String::from("FIXTURE"))]);
+        for extension in [PROFILE_EXTENSION, PERSON_EXTENSION] {
+            let file_name = format!("{FIXTURE_HASH}.{extension}");
+            let imported = fs::read_to_string(fixture_dir().join(&file_name)).unwrap();
+            let exported = fs::read_to_string(export_dir.join(&file_name)).unwrap();
+            assert_eq!(parse(&exported), parse(&imported), "{file_name} changed on the way through");
+        }
+        fs::remove_dir_all(&export_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn imported_profiles_export_unchanged() {
+        round_trip(ItemStorage::Json).await;
+    }
+
+    #[tokio::test]
+    async fn imported_profiles_export_unchanged_from_item_rows() {
+        round_trip(ItemStorage::Table).await;
+    }
+}
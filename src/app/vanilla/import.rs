@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    TransactionTrait,
+};
+use validator::Validate;
+
+use super::super::hasher::rwr1_hash_username;
+use super::super::config::ItemStorage;
+use super::super::profile_server::extras::capture_extras;
+use super::super::profile_server::util::{make_account_model, upsert_account};
+use super::super::profile_server::xml::{PersonXml, PlayerXml, ProfileXml};
+use super::{PERSON_EXTENSION, PROFILE_EXTENSION};
+use entity::{Account, AccountModel, Player, PlayerActiveModel, RealmModel};
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: Vec<(i64, String)>,
+    pub overwritten: Vec<(i64, String)>,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Debug)]
+pub struct ImportConflict {
+    pub file: String,
+    pub reason: String,
+}
+
+enum Outcome {
+    Imported,
+    Overwritten,
+    Conflict(String),
+}
+
+pub async fn import_profiles(
+    db: &DatabaseConnection,
+    realm: &RealmModel,
+    dir: &Path,
+    dry_run: bool,
+    overwrite: bool,
+    item_storage: ItemStorage,
+) -> anyhow::Result<ImportReport> {
+    // pair the files up by their stem, which the game names after the player's hash
+    let mut stems = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_player_file = path
+            .extension()
+            .is_some_and(|ext| ext == PROFILE_EXTENSION || ext == PERSON_EXTENSION);
+        if let (true, Some(stem)) = (is_player_file, path.file_stem().and_then(|stem| stem.to_str())) {
+            stems.insert(stem.to_owned());
+        }
+    }
+
+    let mut report = ImportReport::default();
+    // a dry run does all the same work in a transaction that is then thrown away
+    let txn = db.begin().await?;
+    for stem in stems {
+        let player_xml = match read_player_files(dir, &stem) {
+            Ok(player_xml) => player_xml,
+            Err(reason) => {
+                report.conflicts.push(ImportConflict { file: stem, reason });
+                continue;
+            }
+        };
+        let username = player_xml.profile.username.to_owned();
+        match import_player(&txn, realm, &player_xml, overwrite, item_storage).await? {
+            Outcome::Imported => report.imported.push((player_xml.hash, username)),
+            Outcome::Overwritten => report.overwritten.push((player_xml.hash, username)),
+            Outcome::Conflict(reason) => report.conflicts.push(ImportConflict { file: stem, reason }),
+        }
+    }
+    match dry_run {
+        true => txn.rollback().await?,
+        false => txn.commit().await?,
+    }
+    Ok(report)
+}
+
+fn read_player_files(dir: &Path, stem: &str) -> Result<PlayerXml, String> {
+    let hash: i64 = stem
+        .parse()
+        .ok()
+        .filter(|hash| (1..=u32::MAX as i64).contains(hash))
+        .ok_or_else(|| String::from("file name is not a player hash"))?;
+    let read = |extension: &str| {
+        let path = dir.join(format!("{stem}.{extension}"));
+        fs::read_to_string(&path).map_err(|err| format!("could not read '{}': {err}", path.display()))
+    };
+    let (profile_xml, person_xml) = (read(PROFILE_EXTENSION)?, read(PERSON_EXTENSION)?);
+    let profile: ProfileXml = quick_xml::de::from_str(&profile_xml)
+        .map_err(|err| format!("could not parse .{PROFILE_EXTENSION}: {err}"))?;
+    let person: PersonXml = quick_xml::de::from_str(&person_xml)
+        .map_err(|err| format!("could not parse .{PERSON_EXTENSION}: {err}"))?;
+    // the profile file brings the profile and stats extras, the person file those of the person
+    let capture = |xml: &str, extension: &str| {
+        capture_extras(xml)
+            .map(|mut extras| extras.pop().unwrap_or_default())
+            .map_err(|err| format!("could not parse .{extension}: {err}"))
+    };
+    let mut extras = capture(&profile_xml, PROFILE_EXTENSION)?;
+    extras.person = capture(&person_xml, PERSON_EXTENSION)?.person;
+    let player_xml = PlayerXml {
+        hash,
+        rid: profile.rid.to_owned(),
+        person,
+        profile,
+        extras,
+    };
+    player_xml
+        .validate()
+        .map_err(|err| format!("invalid: {}", err.to_string().replace('\n', ", ")))?;
+    let expected_hash = rwr1_hash_username(&player_xml.profile.username);
+    if expected_hash != hash {
+        return Err(format!(
+            "username '{}' hashes to {expected_hash}, not {hash}",
+            player_xml.profile.username
+        ));
+    }
+    Ok(player_xml)
+}
+
+async fn import_player(
+    txn: &DatabaseTransaction,
+    realm: &RealmModel,
+    player_xml: &PlayerXml,
+    overwrite: bool,
+    item_storage: ItemStorage,
+) -> anyhow::Result<Outcome> {
+    let profile = &player_xml.profile;
+    match Player::find_by_id(player_xml.hash).one(txn).await? {
+        // the same player enlisted elsewhere (e.g. another realm) is fine, a different one is not
+        Some(player) if player.sid != profile.sid || player.rid != profile.rid => {
+            return Ok(Outcome::Conflict(format!(
+                "player '{}' [hash:{}] is already enlisted with sid {} and a different rid",
+                player.username, player.hash, player.sid
+            )));
+        }
+        Some(_) => {}
+        None => {
+            let new_player = PlayerActiveModel {
+                hash: ActiveValue::Set(player_xml.hash),
+                username: ActiveValue::Set(profile.username.to_owned()),
+                sid: ActiveValue::Set(profile.sid),
+                rid: ActiveValue::Set(profile.rid.to_owned()),
+            };
+            new_player.insert(txn).await?;
+        }
+    }
+
+    let account_exists = Account::find_by_id((realm.id, player_xml.hash)).one(txn).await?.is_some();
+    if account_exists && !overwrite {
+        return Ok(Outcome::Conflict(format!(
+            "player '{}' already has an account in realm '{}'",
+            profile.username, realm.name
+        )));
+    }
+    let account = match make_account_model(realm.id, player_xml) {
+        Ok(account) => account,
+        Err(err) => return Ok(Outcome::Conflict(err.to_string())),
+    };
+    let account: AccountModel = account.try_into()?;
+    upsert_account(txn, &account, item_storage).await?;
+    Ok(match account_exists {
+        true => Outcome::Overwritten,
+        false => Outcome::Imported,
+    })
+}
@@ -0,0 +1,7 @@
+// the vanilla rwr server keeps a <hash>.profile and a <hash>.person file per player in its save directory
+pub mod export;
+pub mod import;
+
+pub const PROFILE_EXTENSION: &str = "profile";
+pub const PERSON_EXTENSION: &str = "person";
+pub const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="utf-8"?>"#;
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::Utc;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ActiveValue, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    FromQueryResult, IdenStatic, IntoActiveModel, Iterable, PaginatorTrait, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+
+use super::config::ItemStorage;
+use super::profile_server::util::{get_account_from_db, get_realm_from_db, upsert_account};
+use super::profile_server::json::upgrade_account_blobs;
+use super::profile_server::xml::GetProfileDataXml;
+use entity::{Account, AccountColumn, AccountModel, Player, PlayerColumn, PlayerModel, Realm, RealmActiveModel, RealmColumn};
+
+// värväytyä-py stores realms, players and accounts in tables of the same shape that marshalrwr started out with,
+// so the entities can read from it as long as we stick to the columns it has
+#[derive(Debug, FromQueryResult)]
+struct SourceRealm {
+    id: i32,
+    name: String,
+    digest: String,
+}
+
+#[derive(Debug, Default)]
+pub struct TableSummary {
+    pub source: u64,
+    pub inserted: u64,
+    pub matched: u64,
+    pub overwritten: u64,
+    pub skipped: u64,
+    pub conflicts: Vec<String>,
+}
+
+impl TableSummary {
+    pub fn reconciled(&self) -> bool {
+        // every source row must be accounted for exactly once
+        self.source
+            == self.inserted + self.matched + self.overwritten + self.skipped + self.conflicts.len() as u64
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReconciliationSummary {
+    pub realms: TableSummary,
+    pub players: TableSummary,
+    pub accounts: TableSummary,
+}
+
+pub async fn import_database(
+    source: &DatabaseConnection,
+    db: &DatabaseConnection,
+    dry_run: bool,
+    overwrite: bool,
+    item_storage: ItemStorage,
+) -> anyhow::Result<ReconciliationSummary> {
+    let mut summary = ReconciliationSummary::default();
+    // a dry run does all the same work in a transaction that is then thrown away
+    let txn = db.begin().await?;
+    let realm_ids = import_realms(source, &txn, &mut summary.realms).await?;
+    let players = import_players(source, &txn, &mut summary.players).await?;
+    import_accounts(source, &txn, &realm_ids, &players, overwrite, item_storage, &mut summary.accounts).await?;
+    match dry_run {
+        true => txn.rollback().await?,
+        false => txn.commit().await?,
+    }
+    Ok(summary)
+}
+
+async fn import_realms(
+    source: &DatabaseConnection,
+    txn: &DatabaseTransaction,
+    summary: &mut TableSummary,
+) -> anyhow::Result<HashMap<i32, (i32, String)>> {
+    // realm ids are assigned afresh, so keep track of where each source realm ended up
+    let mut realm_ids = HashMap::new();
+    let source_realms = Realm::find()
+        .select_only()
+        .columns([RealmColumn::Id, RealmColumn::Name, RealmColumn::Digest])
+        .order_by_asc(RealmColumn::Id)
+        .into_model::<SourceRealm>()
+        .all(source)
+        .await?;
+    for source_realm in source_realms {
+        summary.source += 1;
+        let realm = match get_realm_from_db(txn, &source_realm.name).await? {
+            Some(realm) if realm.digest != source_realm.digest => {
+                summary.conflicts.push(format!(
+                    "realm '{}' already exists with a different digest, its accounts are skipped",
+                    source_realm.name
+                ));
+                continue;
+            }
+            Some(realm) => {
+                summary.matched += 1;
+                realm
+            }
+            None => {
+                let new_realm = RealmActiveModel {
+                    name: ActiveValue::Set(source_realm.name.to_owned()),
+                    digest: ActiveValue::Set(source_realm.digest.to_owned()),
+                    created_by: ActiveValue::Set(Some(String::from("varvaytya-py"))),
+                    created_at: ActiveValue::Set(Some(Utc::now())),
+                    ..Default::default()
+                };
+                summary.inserted += 1;
+                new_realm.insert(txn).await?
+            }
+        };
+        realm_ids.insert(source_realm.id, (realm.id, realm.name));
+    }
+    Ok(realm_ids)
+}
+
+async fn import_players(
+    source: &DatabaseConnection,
+    txn: &DatabaseTransaction,
+    summary: &mut TableSummary,
+) -> anyhow::Result<HashMap<i64, Arc<PlayerModel>>> {
+    // only players that were inserted or matched may have their accounts imported
+    let mut players = HashMap::new();
+    // pages are only stable in a fixed order, postgres is free to hand back rows in any order otherwise
+    let mut pages = Player::find().order_by_asc(PlayerColumn::Hash).paginate(source, 256);
+    while let Some(source_players) = pages.fetch_and_next().await? {
+        for source_player in source_players {
+            summary.source += 1;
+            match Player::find_by_id(source_player.hash).one(txn).await? {
+                Some(player) if player.sid != source_player.sid || player.rid != source_player.rid => {
+                    summary.conflicts.push(format!(
+                        "player '{}' [hash:{}] already enlisted with a different sid or rid, their accounts are skipped",
+                        source_player.username, source_player.hash
+                    ));
+                    continue;
+                }
+                Some(_) => summary.matched += 1,
+                None => {
+                    // the rid is kept as-is, otherwise the game clients would be locked out of their profiles
+                    source_player.clone().into_active_model().reset_all().insert(txn).await?;
+                    summary.inserted += 1;
+                }
+            }
+            players.insert(source_player.hash, Arc::new(source_player));
+        }
+    }
+    Ok(players)
+}
+
+async fn import_accounts(
+    source: &DatabaseConnection,
+    txn: &DatabaseTransaction,
+    realm_ids: &HashMap<i32, (i32, String)>,
+    players: &HashMap<i64, Arc<PlayerModel>>,
+    overwrite: bool,
+    item_storage: ItemStorage,
+    summary: &mut TableSummary,
+) -> anyhow::Result<()> {
+    // värväytyä-py has no extras column, its accounts start out without any
+    let mut pages = Account::find()
+        .select_only()
+        .columns(AccountColumn::iter().filter(|column| !matches!(column, AccountColumn::Extras)))
+        .column_as(Expr::val(""), AccountColumn::Extras.as_str())
+        .order_by_asc(AccountColumn::RealmId)
+        .order_by_asc(AccountColumn::Hash)
+        .into_model::<AccountModel>()
+        .paginate(source, 256);
+    while let Some(source_accounts) = pages.fetch_and_next().await? {
+        for mut account in source_accounts {
+            summary.source += 1;
+            // accounts of conflicting realms and players were already reported against those tables
+            let (Some((realm_id, realm_name)), Some(player)) =
+                (realm_ids.get(&account.realm_id), players.get(&account.hash))
+            else {
+                summary.skipped += 1;
+                continue;
+            };
+            account.realm_id = *realm_id;
+            let account_name = format!("account ('{realm_name}', '{}')", player.username);
+            // the json columns are brought up to the current schema, so make sure marshalrwr can actually read them
+            let decoded = upgrade_account_blobs(&mut account)
+                .and_then(|_| GetProfileDataXml::new(player, &Arc::new(account.clone())));
+            if let Err(err) = decoded {
+                summary.conflicts.push(format!("{account_name} could not be decoded: {err}"));
+                continue;
+            }
+            // compare against the existing account with its items in place and in the same schema
+            let mut existing = get_account_from_db(txn, account.realm_id, account.hash).await?;
+            if let Some(existing) = existing.as_mut() {
+                upgrade_account_blobs(existing)?;
+            }
+            match existing {
+                Some(existing) if existing == account => summary.matched += 1,
+                Some(_) if overwrite => {
+                    upsert_account(txn, &account, item_storage).await?;
+                    summary.overwritten += 1;
+                }
+                Some(_) => summary.conflicts.push(format!("{account_name} already exists and differs")),
+                None => {
+                    upsert_account(txn, &account, item_storage).await?;
+                    summary.inserted += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use sea_orm::{ConnectOptions, ConnectionTrait, Database};
+
+    use super::*;
+    use crate::app::testing::test_dbs;
+
+    const FIXTURE_HASH: i64 = 3235692716;
+
+    async fn varvaytya_db() -> DatabaseConnection {
+        let mut options = ConnectOptions::new(String::from("sqlite::memory:"));
+        options.max_connections(1).sqlx_logging(false);
+        let source = Database::connect(options).await.unwrap();
+        let sql_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test/fixtures/varvaytya/varvaytya.sql");
+        let sql = std::fs::read_to_string(sql_path).unwrap();
+        for statement in sql.split(";\n").map(str::trim).filter(|statement| !statement.is_empty()) {
+            let statement: String = statement.lines().filter(|line| !line.starts_with("--")).collect();
+            source.execute_unprepared(&statement).await.unwrap();
+        }
+        source
+    }
+
+    fn counts(table: &TableSummary) -> [u64; 5] {
+        assert!(table.conflicts.is_empty(), "{:?}", table.conflicts);
+        assert!(table.reconciled());
+        [table.source, table.inserted, table.matched, table.overwritten, table.skipped]
+    }
+
+    #[tokio::test]
+    async fn varvaytya_databases_import() {
+        let source = varvaytya_db().await;
+        for db in test_dbs().await {
+            // the realm was already created by a game server, with the same digest
+            let realm = RealmActiveModel {
+                name: ActiveValue::Set(String::from("INCURSION")),
+                digest: ActiveValue::Set("a".repeat(64)),
+                ..Default::default()
+            };
+            let realm = realm.insert(&db).await.unwrap();
+
+            let summary = import_database(&source, &db, false, false, ItemStorage::Json).await.unwrap();
+            assert_eq!(counts(&summary.realms), [2, 1, 1, 0, 0]);
+            assert_eq!(counts(&summary.players), [2, 2, 0, 0, 0]);
+            assert_eq!(counts(&summary.accounts), [3, 3, 0, 0, 0]);
+
+            // the source realm ids (3 and 7) are mapped onto ours and the json comes out in the current schema
+            let account = get_account_from_db(&db, realm.id, FIXTURE_HASH).await.unwrap().unwrap();
+            assert_eq!(account.loadout, r#"{"v":1,"slots":[{"s":0,"i":3,"k":"ak47.weapon","a":1},{"s":1,"i":-1,"k":"","a":0}]}"#);
+            assert_eq!(account.stash, r#"{"v":1,"items":[{"c":3,"i":0,"k":"vest1.carry_item","a":5}]}"#);
+            assert_eq!(account.kill_combos, r#"{"v":1,"entries":[[2,3],[3,1]]}"#);
+            assert_eq!((account.kills, account.time_played, account.authority), (120, 7200, 10.25));
+            let pacific = get_realm_from_db(&db, "PACIFIC").await.unwrap().unwrap();
+            let account = get_account_from_db(&db, pacific.id, FIXTURE_HASH).await.unwrap().unwrap();
+            assert_eq!(account.kill_combos, r#"{"v":1,"entries":[]}"#);
+
+            // importing again finds everything already in place
+            let summary = import_database(&source, &db, false, false, ItemStorage::Json).await.unwrap();
+            assert_eq!(counts(&summary.realms), [2, 0, 2, 0, 0]);
+            assert_eq!(counts(&summary.players), [2, 0, 2, 0, 0]);
+            assert_eq!(counts(&summary.accounts), [3, 0, 3, 0, 0]);
+        }
+    }
+}
@@ -1,8 +1,9 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.0
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "account")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
@@ -43,6 +44,9 @@ pub struct Model {
     pub longest_death_streak: i32,
     pub kill_combos: String,
     pub criteria_monitors: String,
+    // older history and anomaly snapshots were taken before there was an extras column
+    #[serde(default)]
+    pub extras: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,10 +2,25 @@ pub mod prelude;
 pub mod realm;
 pub mod player;
 pub mod account;
+pub mod account_history;
+pub mod account_anomaly;
+pub mod account_item;
+pub mod realm_digest_audit;
+pub mod ban;
 
 pub use prelude::Realm;
 pub use realm::{Model as RealmModel, ActiveModel as RealmActiveModel, Column as RealmColumn};
 pub use prelude::Player;
 pub use player::{Model as PlayerModel, ActiveModel as PlayerActiveModel, Column as PlayerColumn};
 pub use prelude::Account;
-pub use account::{Model as AccountModel, ActiveModel as AccountActiveModel, Column as AccountColumn};
\ No newline at end of file
+pub use account::{Model as AccountModel, ActiveModel as AccountActiveModel, Column as AccountColumn};
+pub use prelude::RealmDigestAudit;
+pub use realm_digest_audit::{Model as RealmDigestAuditModel, ActiveModel as RealmDigestAuditActiveModel, Column as RealmDigestAuditColumn};
+pub use prelude::AccountHistory;
+pub use account_history::{Model as AccountHistoryModel, ActiveModel as AccountHistoryActiveModel, Column as AccountHistoryColumn};
+pub use prelude::AccountAnomaly;
+pub use account_anomaly::{Model as AccountAnomalyModel, ActiveModel as AccountAnomalyActiveModel, Column as AccountAnomalyColumn};
+pub use prelude::AccountItem;
+pub use account_item::{Model as AccountItemModel, ActiveModel as AccountItemActiveModel, Column as AccountItemColumn};
+pub use prelude::Ban;
+pub use ban::{Model as BanModel, ActiveModel as BanActiveModel, Column as BanColumn};
@@ -3,3 +3,8 @@
 pub use super::account::Entity as Account;
 pub use super::player::Entity as Player;
 pub use super::realm::Entity as Realm;
+pub use super::realm_digest_audit::Entity as RealmDigestAudit;
+pub use super::account_history::Entity as AccountHistory;
+pub use super::account_anomaly::Entity as AccountAnomaly;
+pub use super::account_item::Entity as AccountItem;
+pub use super::ban::Entity as Ban;
@@ -9,6 +9,10 @@ pub struct Model {
     pub id: i32,
     pub name: String,
     pub digest: String,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTimeUtc>,
+    pub previous_digest: Option<String>,
+    pub previous_digest_expires_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,56 +1,175 @@
 use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
-use sea_orm::Database;
+use clap::Parser;
+use sea_orm::{Database, DatabaseConnection};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
 use tower_http::trace::TraceLayer;
 
 mod app;
-use app::config::AppConfiguration;
-use app::profile_server::{get::rwr1_get_profile_handler, set::rwr1_set_profile_handler};
+use app::admin::admin_router;
+use app::catalog::ItemCatalog;
+use app::cli::{Cli, Command};
+use app::config::{AppConfiguration, ItemStorage};
+use app::health::{healthz_handler, readyz_handler};
+use app::client_addr::PeerInfo;
+use app::listener::{bind_listener, ProxyProtocolIncoming};
+use app::metrics::{metrics_handler, track_requests};
+use app::profile_server::account_items::count_account_items;
+use app::profile_server::{
+    get::rwr1_get_profile_handler, set::rwr1_set_profile_handler, util::seed_configured_realms,
+};
+use app::reload::spawn_config_reload;
 use app::signalling::shutdown_signal;
 use app::state::AppState;
-use app::tracing::init_tracing_subscriber;
-use app::{DB_DEFAULT_URL, VERSION};
+use app::tracing::{init_tracing_subscriber, make_request_span};
+use app::VERSION;
 
 use migration::{Migrator, MigratorTrait};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    init_tracing_subscriber();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+    // the configuration decides where and how we log, so it comes first
+    let app_config = AppConfiguration::build()?;
+    init_tracing_subscriber(matches!(command, Command::Serve), &app_config)?;
 
     tracing::info!("starting marshalrwr [v{}]", VERSION.unwrap_or("n/a"));
-    tracing::info!("loading configuration...");
-    let app_config = AppConfiguration::build()?;
     tracing::debug!("{app_config:?}");
 
     tracing::debug!("setting up application state...");
-    let db_connection = Database::connect(format!("{DB_DEFAULT_URL}?mode=rwc")).await?;
+    let db_connection = Database::connect(&app_config.db_url).await?;
+
+    // the migrate subcommand is in charge of migrations itself
+    if !matches!(command, Command::Migrate { .. }) {
+        tracing::info!("performing migrations (if any)... :D");
+        Migrator::up(&db_connection, None).await?;
+    }
+
+    match command {
+        Command::Serve => serve(app_config, db_connection).await,
+        command => app::cli::run(command, &app_config, &db_connection).await,
+    }
+}
 
-    tracing::info!("performing migrations (if any)... :D");
-    Migrator::up(&db_connection, None).await?;
+async fn serve(app_config: AppConfiguration, db_connection: DatabaseConnection) -> anyhow::Result<()> {
+    let listen_addrs: Vec<SocketAddr> = app_config
+        .listen_addrs
+        .iter()
+        .map(|ip| SocketAddr::new(*ip, app_config.listen_port))
+        .collect();
+    if listen_addrs.is_empty() {
+        anyhow::bail!("no listen_addrs configured, refusing to start");
+    }
 
-    let app_state = AppState::new(app_config, db_connection);
+    let proxy_protocol = app_config.proxy_protocol;
+    let allowed_hosts = app_config.hostnames();
+    let hosts_refresh_interval = Duration::from_secs(app_config.ps_allowed_hosts_refresh_secs);
+    let item_catalog = match &app_config.ps_item_catalog {
+        Some(path) => {
+            tracing::info!("loading item catalog from '{}'...", path.display());
+            let item_catalog = ItemCatalog::load(path)?;
+            tracing::info!("item catalog has {} item(s)", item_catalog.len());
+            Some(item_catalog)
+        }
+        None => None,
+    };
+    // json mode never looks after account_item rows, which would otherwise be served in place of the json columns
+    if app_config.ps_item_storage == ItemStorage::Json && count_account_items(&db_connection).await? > 0 {
+        anyhow::bail!("ps_item_storage is \"json\" but account_item holds items, run `marshalrwr move-items json` first");
+    }
+    let app_state = AppState::new(app_config, db_connection, item_catalog);
+
+    // resolve any hostnames in the ip allowlist now and then keep them fresh in the background
+    tracing::info!("resolving {} allowlist host(s)...", allowed_hosts.len());
+    app_state.resolver.set_hosts(allowed_hosts).await;
+    app_state.resolver.spawn_refresh(hosts_refresh_interval);
+
+    seed_configured_realms(&app_state).await?;
+    // pick up changes to the config file (or a SIGHUP) without dropping any requests
+    spawn_config_reload(app_state.clone());
 
     // build our application with a route and add the tower-http tracing layer
-    let application_router = Router::new()
+    let mut application_router = Router::new()
         .route("/get_profile.php", get(rwr1_get_profile_handler))
-        .route("/set_profile.php", post(rwr1_set_profile_handler))
-        .with_state(app_state)
-        .layer(TraceLayer::new_for_http());
-
-    // run it
-    let addr = SocketAddr::from(([127, 0, 0, 1], 4321));
-    tracing::info!("listening on {}...", addr);
-    axum::Server::bind(&addr)
-        .serve(application_router.into_make_service_with_connect_info::<SocketAddr>())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+        .route("/set_profile.php", post(rwr1_set_profile_handler));
+    // the admin api only exists when there is a token to protect it with
+    if app_state.config().admin_token.is_some() {
+        tracing::info!("mounting admin api at /admin");
+        application_router = application_router.nest("/admin", admin_router(app_state.clone()));
+    }
+    // count and time every request to the routes above, by route
+    application_router =
+        application_router.route_layer(middleware::from_fn_with_state(app_state.clone(), track_requests));
+    // for supervisors and load balancers, left out of the request metrics like /metrics below
+    application_router = application_router
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler));
+    // the metrics are only scraped from the addresses allowed to, and not at all if there aren't any
+    if !app_state.config().metrics_allowed_ips.is_empty() {
+        tracing::info!("mounting prometheus metrics at /metrics");
+        application_router = application_router.route("/metrics", get(metrics_handler));
+    }
+    let application_router = application_router
+        .with_state(app_state.clone())
+        .layer(TraceLayer::new_for_http().make_span_with(make_request_span));
+
+    // the shutdown signal is received once and then broadcast to every listener
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let shutdown_state = app_state.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        // read at the last moment, the drain period may have been reloaded since startup
+        let drain_period = Duration::from_secs(shutdown_state.config().shutdown_drain_secs);
+        let draining = &shutdown_state.draining;
+        // readiness fails from here on, keep serving for a while so that whatever routes to us can notice
+        draining.store(true, Ordering::Relaxed);
+        if !drain_period.is_zero() {
+            tracing::info!("draining for {}s before closing the listeners...", drain_period.as_secs());
+            tokio::time::sleep(drain_period).await;
+        }
+        let _ = shutdown_tx.send(true);
+    });
+
+    // run it, on every configured address
+    let mut servers = JoinSet::new();
+    for addr in listen_addrs {
+        let listener = bind_listener(addr)?;
+        let make_service = application_router
+            .clone()
+            .into_make_service_with_connect_info::<PeerInfo>();
+        let mut shutdown_rx = shutdown_rx.clone();
+        let graceful_shutdown = async move {
+            let _ = shutdown_rx.changed().await;
+        };
+        if proxy_protocol {
+            tracing::info!("listening on {} (PROXY protocol from trusted proxies)...", addr);
+            let incoming = ProxyProtocolIncoming::new(listener, app_state.clone())?;
+            let server = axum::Server::builder(incoming)
+                .serve(make_service)
+                .with_graceful_shutdown(graceful_shutdown);
+            servers.spawn(server);
+        } else {
+            tracing::info!("listening on {}...", addr);
+            let server = axum::Server::from_tcp(listener)?
+                .serve(make_service)
+                .with_graceful_shutdown(graceful_shutdown);
+            servers.spawn(server);
+        }
+    }
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
 
     // salute the fallen
     tracing::info!("o7");
     Ok(())
-}
\ No newline at end of file
+}
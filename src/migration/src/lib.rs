@@ -8,6 +8,10 @@ pub enum Realm {
     Id,
     Name,
     Digest,
+    CreatedBy,
+    CreatedAt,
+    PreviousDigest,
+    PreviousDigestExpiresAt,
 }
 
 #[derive(Iden)]
@@ -23,6 +27,14 @@ pub enum Player {
 mod m20230213_195206_create_realm_table;
 mod m20230222_020006_create_player_table;
 mod m20230223_212333_create_account_table;
+mod m20261017_063012_alter_account_column_types;
+mod m20261017_083012_add_realm_created_by;
+mod m20261017_101544_add_realm_digest_rotation;
+mod m20261017_134207_create_account_history_table;
+mod m20261017_161930_create_account_anomaly_table;
+mod m20261017_190412_create_account_item_table;
+mod m20261017_221547_add_account_extras;
+mod m20261017_235012_create_ban_table;
 
 pub struct Migrator;
 
@@ -33,6 +45,14 @@ impl MigratorTrait for Migrator {
             Box::new(m20230213_195206_create_realm_table::Migration),
             Box::new(m20230222_020006_create_player_table::Migration),
             Box::new(m20230223_212333_create_account_table::Migration),
+            Box::new(m20261017_063012_alter_account_column_types::Migration),
+            Box::new(m20261017_083012_add_realm_created_by::Migration),
+            Box::new(m20261017_101544_add_realm_digest_rotation::Migration),
+            Box::new(m20261017_134207_create_account_history_table::Migration),
+            Box::new(m20261017_161930_create_account_anomaly_table::Migration),
+            Box::new(m20261017_190412_create_account_item_table::Migration),
+            Box::new(m20261017_221547_add_account_extras::Migration),
+            Box::new(m20261017_235012_create_ban_table::Migration),
         ]
     }
 }
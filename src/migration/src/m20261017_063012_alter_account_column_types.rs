@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Account {
+    Table,
+    MaxAuthorityReached,
+    Authority,
+    JobPoints,
+    Loadout,
+    Backpack,
+    Stash,
+    DistanceMoved,
+    RankProgression,
+    KillCombos,
+    CriteriaMonitors,
+}
+
+const DOUBLE_COLUMNS: [Account; 5] = [
+    Account::MaxAuthorityReached,
+    Account::Authority,
+    Account::JobPoints,
+    Account::DistanceMoved,
+    Account::RankProgression,
+];
+
+const TEXT_COLUMNS: [Account; 5] = [
+    Account::Loadout,
+    Account::Backpack,
+    Account::Stash,
+    Account::KillCombos,
+    Account::CriteriaMonitors,
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the entity maps these columns onto f64 and String, which the float and json columns of the account
+        // table only satisfy on sqlite, where both pairs share the same storage (real and text) anyway
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+        let mut alter = Table::alter().table(Account::Table).to_owned();
+        for column in DOUBLE_COLUMNS {
+            alter.modify_column(ColumnDef::new(column).double().not_null());
+        }
+        for column in TEXT_COLUMNS {
+            alter.modify_column(ColumnDef::new(column).text().not_null());
+        }
+        manager.alter_table(alter).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Postgres {
+            return Ok(());
+        }
+        // text only becomes json with an explicit cast, which sea-query can't put in an alter table
+        let mut changes: Vec<String> = DOUBLE_COLUMNS
+            .iter()
+            .map(|column| format!("ALTER COLUMN \"{}\" TYPE real", column.to_string()))
+            .collect();
+        changes.extend(TEXT_COLUMNS.iter().map(|column| {
+            let name = column.to_string();
+            format!("ALTER COLUMN \"{name}\" TYPE json USING \"{name}\"::json")
+        }));
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                DbBackend::Postgres,
+                format!("ALTER TABLE \"{}\" {}", Account::Table.to_string(), changes.join(", ")),
+            ))
+            .await?;
+
+        Ok(())
+    }
+}
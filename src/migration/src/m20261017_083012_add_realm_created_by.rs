@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+use super::Realm;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // record who created each realm (an ip address, or "cli"/"config" for seeded realms) and when
+        // note: sqlite can only add one column per alter table statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Realm::Table)
+                    .add_column(ColumnDef::new(Realm::CreatedBy).string_len(64).null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Realm::Table)
+                    .add_column(ColumnDef::new(Realm::CreatedAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(Realm::Table).drop_column(Realm::CreatedAt).to_owned())
+            .await?;
+        manager
+            .alter_table(Table::alter().table(Realm::Table).drop_column(Realm::CreatedBy).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
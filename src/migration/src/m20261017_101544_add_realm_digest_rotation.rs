@@ -0,0 +1,99 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+use super::Realm;
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum RealmDigestAudit {
+    Table,
+    Id,
+    RealmId,
+    Event,
+    OldDigest,
+    NewDigest,
+    Actor,
+    CreatedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the digest being rotated out stays valid until previous_digest_expires_at
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Realm::Table)
+                    .add_column(ColumnDef::new(Realm::PreviousDigest).string_len(64).null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Realm::Table)
+                    .add_column(ColumnDef::new(Realm::PreviousDigestExpiresAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // create the realm digest audit table
+        manager
+            .create_table(
+                Table::create()
+                    .table(RealmDigestAudit::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RealmDigestAudit::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RealmDigestAudit::RealmId).integer().not_null())
+                    .col(ColumnDef::new(RealmDigestAudit::Event).string_len(16).not_null())
+                    .col(ColumnDef::new(RealmDigestAudit::OldDigest).string_len(64).null())
+                    .col(ColumnDef::new(RealmDigestAudit::NewDigest).string_len(64).null())
+                    .col(ColumnDef::new(RealmDigestAudit::Actor).string_len(64).null())
+                    .col(ColumnDef::new(RealmDigestAudit::CreatedAt).timestamp_with_time_zone().not_null())
+                    .foreign_key(ForeignKey::create().name("fk-realm_digest_audit-realm_id")
+                        .from(RealmDigestAudit::Table, RealmDigestAudit::RealmId)
+                        .to(Realm::Table, Realm::Id))
+                    .to_owned(),
+            )
+            .await?;
+
+        // create realm digest audit realm id index
+        manager.create_index(
+            Index::create()
+                .name("idx_realm_digest_audit_realm_id")
+                .table(RealmDigestAudit::Table)
+                .col(RealmDigestAudit::RealmId)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // drop the realm digest audit realm id index
+        manager.drop_index(Index::drop().name("idx_realm_digest_audit_realm_id").table(RealmDigestAudit::Table).to_owned())
+            .await?;
+
+        // drop the realm digest audit table
+        manager
+            .drop_table(Table::drop().table(RealmDigestAudit::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(Realm::Table).drop_column(Realm::PreviousDigestExpiresAt).to_owned())
+            .await?;
+        manager
+            .alter_table(Table::alter().table(Realm::Table).drop_column(Realm::PreviousDigest).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
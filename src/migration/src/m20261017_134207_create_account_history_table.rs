@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum AccountHistory {
+    Table,
+    Id,
+    RealmId,
+    Hash,
+    CreatedAt,
+    Source,
+    SourceIp,
+    Snapshot,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create the account history table, no foreign keys so that history outlives a reset account
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccountHistory::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AccountHistory::RealmId).integer().not_null())
+                    .col(ColumnDef::new(AccountHistory::Hash).big_integer().not_null())
+                    .col(ColumnDef::new(AccountHistory::CreatedAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(AccountHistory::Source).string_len(16).not_null())
+                    .col(ColumnDef::new(AccountHistory::SourceIp).string_len(45).null())
+                    // the whole account row as json, text rather than json so that it works on sqlite too
+                    .col(ColumnDef::new(AccountHistory::Snapshot).text().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // create account history (realm id, hash) index
+        manager.create_index(
+            Index::create()
+                .name("idx_account_history_realm_id_hash")
+                .table(AccountHistory::Table)
+                .col(AccountHistory::RealmId)
+                .col(AccountHistory::Hash)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // drop the account history (realm id, hash) index
+        manager.drop_index(Index::drop().name("idx_account_history_realm_id_hash").table(AccountHistory::Table).to_owned())
+            .await?;
+
+        // drop the account history table
+        manager
+            .drop_table(Table::drop().table(AccountHistory::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum AccountAnomaly {
+    Table,
+    Id,
+    RealmId,
+    Hash,
+    CreatedAt,
+    Action,
+    Hits,
+    Snapshot,
+    SourceIp,
+    Resolution,
+    ResolvedAt,
+    ResolvedBy,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create the account anomaly table, no foreign keys so that the evidence outlives a reset account
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountAnomaly::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccountAnomaly::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AccountAnomaly::RealmId).integer().not_null())
+                    .col(ColumnDef::new(AccountAnomaly::Hash).big_integer().not_null())
+                    .col(ColumnDef::new(AccountAnomaly::CreatedAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(AccountAnomaly::Action).string_len(16).not_null())
+                    // the rules that were hit and the incoming account row, both as json text
+                    .col(ColumnDef::new(AccountAnomaly::Hits).text().not_null())
+                    .col(ColumnDef::new(AccountAnomaly::Snapshot).text().not_null())
+                    .col(ColumnDef::new(AccountAnomaly::SourceIp).string_len(45).null())
+                    .col(ColumnDef::new(AccountAnomaly::Resolution).string_len(16).null())
+                    .col(ColumnDef::new(AccountAnomaly::ResolvedAt).timestamp_with_time_zone().null())
+                    .col(ColumnDef::new(AccountAnomaly::ResolvedBy).string_len(64).null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // create account anomaly (realm id, hash) index
+        manager.create_index(
+            Index::create()
+                .name("idx_account_anomaly_realm_id_hash")
+                .table(AccountAnomaly::Table)
+                .col(AccountAnomaly::RealmId)
+                .col(AccountAnomaly::Hash)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // drop the account anomaly (realm id, hash) index
+        manager.drop_index(Index::drop().name("idx_account_anomaly_realm_id_hash").table(AccountAnomaly::Table).to_owned())
+            .await?;
+
+        // drop the account anomaly table
+        manager
+            .drop_table(Table::drop().table(AccountAnomaly::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, QueryResult};
+use serde::{Deserialize, Serialize};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Account {
+    Table,
+    RealmId,
+    Hash,
+    Loadout,
+    Backpack,
+    Stash,
+}
+
+#[derive(Iden)]
+enum AccountItem {
+    Table,
+    RealmId,
+    Hash,
+    Container,
+    Position,
+    Slot,
+    Class,
+    ItemIndex,
+    Key,
+    Amount,
+}
+
+// the json shapes of the account loadout and backpack/stash columns, as written by the profile server
+#[derive(Default, Serialize, Deserialize)]
+struct Loadout {
+    slots: Vec<EquippedItem>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EquippedItem {
+    #[serde(rename = "s")]
+    slot: u8,
+    #[serde(rename = "i")]
+    index: i32,
+    #[serde(rename = "k")]
+    key: String,
+    #[serde(rename = "a")]
+    amount: u16,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ItemStore {
+    items: Vec<StoredItem>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredItem {
+    #[serde(rename = "c")]
+    class: u8,
+    #[serde(rename = "i")]
+    index: i32,
+    #[serde(rename = "k")]
+    key: String,
+    #[serde(rename = "a")]
+    amount: u16,
+}
+
+const PAGE_SIZE: u64 = 256;
+
+fn json_err(err: serde_json::Error) -> DbErr {
+    DbErr::Migration(err.to_string())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one row per item, in the order the game sent them so that get_profile can send them back the same way
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountItem::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(AccountItem::RealmId).integer().not_null())
+                    .col(ColumnDef::new(AccountItem::Hash).big_integer().not_null())
+                    .col(ColumnDef::new(AccountItem::Container).string_len(8).not_null())
+                    .col(ColumnDef::new(AccountItem::Position).integer().not_null())
+                    .col(ColumnDef::new(AccountItem::Slot).integer().null())
+                    .col(ColumnDef::new(AccountItem::Class).integer().null())
+                    .col(ColumnDef::new(AccountItem::ItemIndex).integer().not_null())
+                    .col(ColumnDef::new(AccountItem::Key).string().not_null())
+                    .col(ColumnDef::new(AccountItem::Amount).integer().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(AccountItem::RealmId)
+                            .col(AccountItem::Hash)
+                            .col(AccountItem::Container)
+                            .col(AccountItem::Position),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // create account item (realm id, hash, container, key) index, for "who owns what" queries
+        manager.create_index(
+            Index::create()
+                .name("idx_account_item_realm_id_hash_container_key")
+                .table(AccountItem::Table)
+                .col(AccountItem::RealmId)
+                .col(AccountItem::Hash)
+                .col(AccountItem::Container)
+                .col(AccountItem::Key)
+                .to_owned()
+            ).await?;
+        manager.create_index(
+            Index::create()
+                .name("idx_account_item_key")
+                .table(AccountItem::Table)
+                .col(AccountItem::Key)
+                .to_owned()
+            ).await?;
+
+        // copy the items of every existing account into rows, the json columns are left as they are
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+        let mut offset = 0;
+        loop {
+            let accounts = db
+                .query_all(
+                    backend.build(
+                        Query::select()
+                            .columns([Account::RealmId, Account::Hash, Account::Loadout, Account::Backpack, Account::Stash])
+                            .from(Account::Table)
+                            .order_by(Account::RealmId, Order::Asc)
+                            .order_by(Account::Hash, Order::Asc)
+                            .limit(PAGE_SIZE)
+                            .offset(offset),
+                    ),
+                )
+                .await?;
+            if accounts.is_empty() {
+                break;
+            }
+            offset += PAGE_SIZE;
+            // one insert per account keeps well clear of the bind parameter limits of both backends
+            for account in accounts.iter() {
+                let mut insert = Query::insert()
+                    .into_table(AccountItem::Table)
+                    .columns([
+                        AccountItem::RealmId,
+                        AccountItem::Hash,
+                        AccountItem::Container,
+                        AccountItem::Position,
+                        AccountItem::Slot,
+                        AccountItem::Class,
+                        AccountItem::ItemIndex,
+                        AccountItem::Key,
+                        AccountItem::Amount,
+                    ])
+                    .to_owned();
+                if add_account_rows(&mut insert, account)? > 0 {
+                    db.execute(backend.build(&insert)).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // accounts stored as rows only have empty json columns, so fold every account's rows back into json first
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+        let items = db
+            .query_all(
+                backend.build(
+                    Query::select()
+                        .columns([
+                            AccountItem::RealmId,
+                            AccountItem::Hash,
+                            AccountItem::Container,
+                            AccountItem::Slot,
+                            AccountItem::Class,
+                            AccountItem::ItemIndex,
+                            AccountItem::Key,
+                            AccountItem::Amount,
+                        ])
+                        .from(AccountItem::Table)
+                        .order_by(AccountItem::RealmId, Order::Asc)
+                        .order_by(AccountItem::Hash, Order::Asc)
+                        .order_by(AccountItem::Container, Order::Asc)
+                        .order_by(AccountItem::Position, Order::Asc),
+                ),
+            )
+            .await?;
+        let mut accounts: BTreeMap<(i32, i64), (Loadout, ItemStore, ItemStore)> = BTreeMap::new();
+        for item in items.iter() {
+            let realm_id: i32 = item.try_get("", "realm_id")?;
+            let hash: i64 = item.try_get("", "hash")?;
+            let container: String = item.try_get("", "container")?;
+            let index: i32 = item.try_get("", "item_index")?;
+            let key: String = item.try_get("", "key")?;
+            let amount = item.try_get::<i32>("", "amount")? as u16;
+            let (loadout, backpack, stash) = accounts.entry((realm_id, hash)).or_default();
+            match container.as_str() {
+                "loadout" => {
+                    let slot = item.try_get::<Option<i32>>("", "slot")?.unwrap_or(0) as u8;
+                    loadout.slots.push(EquippedItem { slot, index, key, amount });
+                }
+                store => {
+                    let class = item.try_get::<Option<i32>>("", "class")?.unwrap_or(0) as u8;
+                    let stored_item = StoredItem { class, index, key, amount };
+                    match store {
+                        "backpack" => backpack.items.push(stored_item),
+                        _ => stash.items.push(stored_item),
+                    }
+                }
+            }
+        }
+        for ((realm_id, hash), (loadout, backpack, stash)) in accounts {
+            db.execute(
+                backend.build(
+                    Query::update()
+                        .table(Account::Table)
+                        .values([
+                            (Account::Loadout, serde_json::to_string(&loadout).map_err(json_err)?.into()),
+                            (Account::Backpack, serde_json::to_string(&backpack).map_err(json_err)?.into()),
+                            (Account::Stash, serde_json::to_string(&stash).map_err(json_err)?.into()),
+                        ])
+                        .and_where(Expr::col(Account::RealmId).eq(realm_id))
+                        .and_where(Expr::col(Account::Hash).eq(hash)),
+                ),
+            )
+            .await?;
+        }
+
+        // drop the account item indexes
+        manager.drop_index(Index::drop().name("idx_account_item_key").table(AccountItem::Table).to_owned())
+            .await?;
+        manager.drop_index(Index::drop().name("idx_account_item_realm_id_hash_container_key").table(AccountItem::Table).to_owned())
+            .await?;
+
+        // drop the account item table
+        manager
+            .drop_table(Table::drop().table(AccountItem::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn add_account_rows(insert: &mut InsertStatement, account: &QueryResult) -> Result<usize, DbErr> {
+    let realm_id: i32 = account.try_get("", "realm_id")?;
+    let hash: i64 = account.try_get("", "hash")?;
+    let loadout: Loadout = serde_json::from_str(&account.try_get::<String>("", "loadout")?).map_err(json_err)?;
+    let backpack: ItemStore = serde_json::from_str(&account.try_get::<String>("", "backpack")?).map_err(json_err)?;
+    let stash: ItemStore = serde_json::from_str(&account.try_get::<String>("", "stash")?).map_err(json_err)?;
+    let mut rows = 0;
+    for (position, item) in loadout.slots.into_iter().enumerate() {
+        insert.values_panic([
+            realm_id.into(),
+            hash.into(),
+            "loadout".into(),
+            (position as i32).into(),
+            Some(item.slot as i32).into(),
+            None::<i32>.into(),
+            item.index.into(),
+            item.key.into(),
+            (item.amount as i32).into(),
+        ]);
+        rows += 1;
+    }
+    for (container, store) in [("backpack", backpack), ("stash", stash)] {
+        for (position, item) in store.items.into_iter().enumerate() {
+            insert.values_panic([
+                realm_id.into(),
+                hash.into(),
+                container.into(),
+                (position as i32).into(),
+                None::<i32>.into(),
+                Some(item.class as i32).into(),
+                item.index.into(),
+                item.key.into(),
+                (item.amount as i32).into(),
+            ]);
+            rows += 1;
+        }
+    }
+    Ok(rows)
+}
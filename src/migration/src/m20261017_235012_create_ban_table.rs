@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Ban {
+    Table,
+    Id,
+    Kind,
+    Target,
+    Realm,
+    Reason,
+    IssuedBy,
+    CreatedAt,
+    ExpiresAt,
+    LiftedAt,
+    LiftedBy,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create the ban table, bans are lifted rather than deleted so that the table doubles as their history
+        manager
+            .create_table(
+                Table::create()
+                    .table(Ban::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Ban::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // what is banned, a sid, a player hash or an ip address/network, as text
+                    .col(ColumnDef::new(Ban::Kind).string_len(8).not_null())
+                    .col(ColumnDef::new(Ban::Target).string_len(64).not_null())
+                    // the realm name the ban is limited to, null bans in every realm
+                    .col(ColumnDef::new(Ban::Realm).string_len(64).null())
+                    .col(ColumnDef::new(Ban::Reason).text().not_null())
+                    .col(ColumnDef::new(Ban::IssuedBy).string_len(64).not_null())
+                    .col(ColumnDef::new(Ban::CreatedAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(Ban::ExpiresAt).timestamp_with_time_zone().null())
+                    .col(ColumnDef::new(Ban::LiftedAt).timestamp_with_time_zone().null())
+                    .col(ColumnDef::new(Ban::LiftedBy).string_len(64).null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // create ban (kind, target) index
+        manager.create_index(
+            Index::create()
+                .name("idx_ban_kind_target")
+                .table(Ban::Table)
+                .col(Ban::Kind)
+                .col(Ban::Target)
+                .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // drop the ban (kind, target) index
+        manager.drop_index(Index::drop().name("idx_ban_kind_target").table(Ban::Table).to_owned())
+            .await?;
+
+        // drop the ban table
+        manager
+            .drop_table(Table::drop().table(Ban::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
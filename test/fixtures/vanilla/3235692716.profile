@@ -0,0 +1,15 @@
+<?xml version="1.0" encoding="utf-8"?>
+<profile game_version="143" username="FIXTURE" digest="" sid="76561198" rid="9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08" squad_tag="ABC" color="0.595 0.476 0.328 1">
+	<stats kills="120" deaths="30" time_played="7200" player_kills="2" teamkills="1" longest_kill_streak="17" targets_destroyed="3" vehicles_destroyed="4" soldiers_healed="5" times_got_healed="6" distance_moved="12345.5" shots_fired="2000" throwables_thrown="12" rank_progression="0.75">
+		<monitor name="kill combo">
+			<entry combo="2" count="3"/>
+			<entry combo="3" count="1"/>
+		</monitor>
+		<monitor name="death streak" longest_death_streak="3"/>
+		<monitor name="squad tag" level="1">
+			<criteria count="4"/>
+			<criteria count="5"/>
+		</monitor>
+		<monitor name="sniper" level="2" custom="1"/>
+	</stats>
+</profile>
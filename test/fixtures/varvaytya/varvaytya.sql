@@ -0,0 +1,15 @@
+-- a värväytyä-py database: its realm, player and account tables, which marshalrwr's first three migrations were
+-- ported from, kept here as they were so that the importer is tested against them rather than against our own schema
+CREATE TABLE "realm" ( "id" integer NOT NULL PRIMARY KEY AUTOINCREMENT, "name" text(32) NOT NULL UNIQUE, "digest" text(64) NOT NULL );
+CREATE INDEX "idx_realm_name" ON "realm" ("name");
+CREATE TABLE "player" ( "hash" integer NOT NULL PRIMARY KEY, "username" text(32) NOT NULL UNIQUE, "sid" integer NOT NULL, "rid" text(64) NOT NULL );
+CREATE INDEX "idx_player_username" ON "player" ("username");
+CREATE INDEX "idx_player_sid" ON "player" ("sid");
+CREATE TABLE "account" ( "realm_id" integer NOT NULL, "hash" integer NOT NULL, "game_version" integer NOT NULL, "squad_tag" text(3) NOT NULL, "max_authority_reached" real NOT NULL, "authority" real NOT NULL, "job_points" real NOT NULL, "faction" integer NOT NULL, "name" text(32) NOT NULL, "soldier_group_id" integer NOT NULL, "soldier_group_name" text(32) NOT NULL, "squad_size_setting" integer NOT NULL, "loadout" text NOT NULL, "backpack" text NOT NULL, "stash" text NOT NULL, "kills" integer NOT NULL, "deaths" integer NOT NULL, "time_played" integer NOT NULL, "player_kills" integer NOT NULL, "teamkills" integer NOT NULL, "longest_kill_streak" integer NOT NULL, "targets_destroyed" integer NOT NULL, "vehicles_destroyed" integer NOT NULL, "soldiers_healed" integer NOT NULL, "distance_moved" real NOT NULL, "shots_fired" integer NOT NULL, "throwables_thrown" integer NOT NULL, "rank_progression" real NOT NULL, "longest_death_streak" integer NOT NULL, "kill_combos" text NOT NULL, "criteria_monitors" text NOT NULL, PRIMARY KEY ("realm_id", "hash"), FOREIGN KEY ("realm_id") REFERENCES "realm" ("id"), FOREIGN KEY ("hash") REFERENCES "player" ("hash") );
+INSERT INTO "realm" ("id", "name", "digest") VALUES (3, 'INCURSION', 'aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa');
+INSERT INTO "realm" ("id", "name", "digest") VALUES (7, 'PACIFIC', 'bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb');
+INSERT INTO "player" ("hash", "username", "sid", "rid") VALUES (3235692716, 'FIXTURE', 76561198, '9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08');
+INSERT INTO "player" ("hash", "username", "sid", "rid") VALUES (2, 'RECRUIT', 76561199, 'cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc');
+INSERT INTO "account" VALUES (3, 3235692716, 143, 'ABC', 12.5, 10.25, 3.125, 0, 'FIXTURE', 0, 'default', 4, '{"slots":[{"s":0,"i":3,"k":"ak47.weapon","a":1},{"s":1,"i":-1,"k":"","a":0}]}', '{"items":[{"c":0,"i":3,"k":"m16a4.weapon","a":2}]}', '{"items":[{"c":3,"i":0,"k":"vest1.carry_item","a":5}]}', 120, 30, 7200, 2, 1, 17, 3, 4, 5, 12345.5, 2000, 12, 0.75, 3, '{"entries":[[2,3],[3,1]]}', '{"monitors":[{"n":"squad tag","l":1,"c":[4,5]}]}');
+INSERT INTO "account" VALUES (7, 3235692716, 143, '', 0.0, 0.0, 0.0, 1, 'FIXTURE', 0, 'default', 0, '{"slots":[]}', '{"items":[]}', '{"items":[]}', 0, 0, 60, 0, 0, 0, 0, 0, 0, 0.0, 0, 0, 0.0, 0, '', '{"monitors":[]}');
+INSERT INTO "account" VALUES (7, 2, 143, '', 1.5, 1.5, 0.0, 1, 'RECRUIT', 0, 'default', 0, '{"slots":[]}', '{"items":[]}', '{"items":[]}', 3, 1, 600, 0, 0, 2, 0, 0, 0, 100.0, 50, 1, 0.1, 1, '{"entries":[]}', '{"monitors":[]}');